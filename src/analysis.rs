@@ -0,0 +1,390 @@
+use std::io::Write;
+
+use crate::middleware::{Scan, ScanKind};
+
+// Reflectance and transmittance are both the ratio I / I0 of a sample scan to a baseline scan; only the geometry differs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DerivedQuantity {
+    Reflectance,
+    Transmittance,
+}
+
+impl DerivedQuantity {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DerivedQuantity::Reflectance => "Reflectance",
+            DerivedQuantity::Transmittance => "Transmittance",
+        }
+    }
+}
+
+/// A dataset on a common position grid with a one-sigma uncertainty for every point.
+#[derive(Clone)]
+pub struct Spectrum {
+    pub positions: Vec<f64>,
+    pub values: Vec<f64>,
+    pub uncertainties: Vec<f64>,
+}
+
+impl Spectrum {
+    /// Writes the spectrum as comma-separated position, value, uncertainty rows.
+    ///
+    /// `header` lines are written first, each prefixed with `#`.
+    pub fn write_csv(&self, path: &str, header: &[String]) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        for line in header {
+            writeln!(file, "# {}", line)?;
+        }
+
+        for i in 0..self.positions.len() {
+            writeln!(
+                file,
+                "{},{},{}",
+                self.positions[i], self.values[i], self.uncertainties[i]
+            )?;
+        }
+
+        file.flush()
+    }
+}
+
+/// Dark level of one detector, taken from every reading in its dark scans.
+pub struct Dark {
+    pub level: f64,
+    pub uncertainty: f64, // Standard error of the mean.
+}
+
+impl Dark {
+    /// Without any dark scans the dark level is taken to be zero.
+    pub fn from_scans(scans: &[Scan]) -> Dark {
+        let readings: Vec<f64> = scans
+            .iter()
+            .filter(|s| s.kind == ScanKind::Dark)
+            .flat_map(|s| s.readings.iter().copied())
+            .collect();
+
+        let (level, sigma) = mean_and_sigma(&readings);
+
+        Dark {
+            level,
+            uncertainty: sigma / (readings.len().max(1) as f64).sqrt(),
+        }
+    }
+}
+
+/// Returns the mean and sample standard deviation. Empty input returns zeros.
+fn mean_and_sigma(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, var.sqrt())
+}
+
+/// Linearly interpolates the (position, value) pairs onto `grid`.
+///
+/// Grid points outside of the recorded range are NaN. Positions need not be sorted.
+pub fn interpolate(positions: &[f64], values: &[f64], grid: &[f64]) -> Vec<f64> {
+    let mut pts: Vec<(f64, f64)> = positions.iter().copied().zip(values.iter().copied()).collect();
+    pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    grid.iter()
+        .map(|&x| {
+            let idx = pts.partition_point(|p| p.0 < x);
+
+            match pts.get(idx) {
+                Some(&(x1, y1)) if x1 == x => y1,
+                Some(&(x1, y1)) if idx > 0 => {
+                    let (x0, y0) = pts[idx - 1];
+                    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+                }
+                _ => f64::NAN,
+            }
+        })
+        .collect()
+}
+
+/// Builds an evenly spaced grid covering the range recorded by every one of `scans`.
+fn common_grid(scans: &[&Scan], step: f64) -> Vec<f64> {
+    let mut start = f64::NEG_INFINITY;
+    let mut end = f64::INFINITY;
+
+    for scan in scans {
+        let min = scan.positions.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = scan.positions.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        start = start.max(min);
+        end = end.min(max);
+    }
+
+    if start > end || step <= 0.0 {
+        return Vec::new();
+    }
+
+    let n = ((end - start) / step + 1e-9).floor() as usize;
+    (0..=n).map(|i| start + i as f64 * step).collect()
+}
+
+/// Averages the dark-corrected signal of all scans of `kind` on `grid`.
+///
+/// With a reference detector each scan is first normalized to it point by point. The uncertainty combines
+/// the scatter between repeated scans with the uncertainty of the dark levels.
+fn signal(
+    sample: &[Scan],
+    reference: Option<&[Scan]>,
+    kind: ScanKind,
+    grid: &[f64],
+) -> Result<Spectrum, String> {
+    let sample_dark = Dark::from_scans(sample);
+    let ref_dark = reference.map(Dark::from_scans);
+
+    // Per-scan signal and corrected reference on the grid.
+    let mut sigs: Vec<Vec<f64>> = Vec::new();
+    let mut refs: Vec<Vec<f64>> = Vec::new();
+
+    for (i, scan) in sample.iter().enumerate().filter(|(_, s)| s.kind == kind) {
+        let s = interpolate(&scan.positions, &scan.readings, grid);
+
+        match (reference, &ref_dark) {
+            (Some(reference), Some(ref_dark)) => {
+                let r_scan = reference.get(i).ok_or_else(|| no_reference(i))?;
+                let r: Vec<f64> = interpolate(&r_scan.positions, &r_scan.readings, grid)
+                    .iter()
+                    .map(|r| r - ref_dark.level)
+                    .collect();
+
+                sigs.push(s.iter().zip(r.iter()).map(|(s, r)| (s - sample_dark.level) / r).collect());
+                refs.push(r);
+            }
+            _ => sigs.push(s.iter().map(|s| s - sample_dark.level).collect()),
+        }
+    }
+
+    let mut values = Vec::with_capacity(grid.len());
+    let mut uncertainties = Vec::with_capacity(grid.len());
+
+    for j in 0..grid.len() {
+        let col: Vec<f64> = sigs.iter().map(|s| s[j]).collect();
+        let (mean, sigma) = mean_and_sigma(&col);
+        let mut var = (sigma / (col.len() as f64).sqrt()).powi(2);
+
+        match &ref_dark {
+            Some(ref_dark) => {
+                let r = mean_and_sigma(&refs.iter().map(|r| r[j]).collect::<Vec<f64>>()).0;
+                var += (sample_dark.uncertainty / r).powi(2) + (mean * ref_dark.uncertainty / r).powi(2);
+            }
+            None => var += sample_dark.uncertainty.powi(2),
+        }
+
+        values.push(mean);
+        uncertainties.push(var.sqrt());
+    }
+
+    Ok(Spectrum {
+        positions: grid.to_vec(),
+        values,
+        uncertainties,
+    })
+}
+
+fn no_reference(scan_idx: usize) -> String {
+    format!("The reference detector has no scan {}.", scan_idx + 1)
+}
+
+/// Computes I / I0 from the scans marked as sample and baseline, after dark subtraction.
+///
+/// `sample` holds the scans of the detector behind the sample. If `reference` is given, it must hold the scans of
+/// the lamp-monitoring detector recorded alongside, index for index. Repeated scans of the same kind are averaged.
+pub fn derive(sample: &[Scan], reference: Option<&[Scan]>, step: f64) -> Result<Spectrum, String> {
+    let marked: Vec<(usize, &Scan)> = sample
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.kind == ScanKind::Baseline || s.kind == ScanKind::Sample)
+        .collect();
+
+    if !marked.iter().any(|(_, s)| s.kind == ScanKind::Baseline) {
+        return Err("No scan is marked as baseline.".to_owned());
+    }
+    if !marked.iter().any(|(_, s)| s.kind == ScanKind::Sample) {
+        return Err("No scan is marked as sample.".to_owned());
+    }
+
+    // Normalized, the signal only exists where the reference was recorded too.
+    let mut recorded: Vec<&Scan> = marked.iter().map(|&(_, s)| s).collect();
+    if let Some(reference) = reference {
+        for &(i, _) in marked.iter() {
+            recorded.push(reference.get(i).ok_or_else(|| no_reference(i))?);
+        }
+    }
+
+    let grid = common_grid(&recorded, step);
+    if grid.is_empty() {
+        return Err("The baseline and sample scans do not share a common range.".to_owned());
+    }
+
+    let i = signal(sample, reference, ScanKind::Sample, &grid)?;
+    let i0 = signal(sample, reference, ScanKind::Baseline, &grid)?;
+
+    let mut values = Vec::with_capacity(grid.len());
+    let mut uncertainties = Vec::with_capacity(grid.len());
+
+    for j in 0..grid.len() {
+        let v = i.values[j] / i0.values[j];
+        let rel = (i.uncertainties[j] / i.values[j]).powi(2) + (i0.uncertainties[j] / i0.values[j]).powi(2);
+
+        values.push(v);
+        uncertainties.push(v.abs() * rel.sqrt());
+    }
+
+    Ok(Spectrum {
+        positions: grid,
+        values,
+        uncertainties,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(kind: ScanKind, positions: &[f64], readings: &[f64]) -> Scan {
+        Scan {
+            kind,
+            positions: positions.to_vec(),
+            readings: readings.to_vec(),
+            ..Scan::default()
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn interpolates_at_and_between_points() {
+        let values = interpolate(&[0.0, 10.0, 20.0], &[1.0, 3.0, 7.0], &[0.0, 5.0, 10.0, 15.0, 20.0]);
+
+        assert_eq!(values, vec![1.0, 2.0, 3.0, 5.0, 7.0]);
+    }
+
+    #[test]
+    fn interpolates_unsorted_positions() {
+        let values = interpolate(&[20.0, 0.0, 10.0], &[7.0, 1.0, 3.0], &[5.0, 15.0]);
+
+        assert_eq!(values, vec![2.0, 5.0]);
+    }
+
+    #[test]
+    fn interpolation_outside_the_range_is_nan() {
+        let values = interpolate(&[0.0, 10.0], &[1.0, 3.0], &[-1.0, 11.0]);
+
+        assert!(values.iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn common_grid_covers_the_overlap() {
+        let a = scan(ScanKind::Sample, &[0.0, 10.0, 20.0], &[0.0; 3]);
+        let b = scan(ScanKind::Baseline, &[5.0, 15.0, 30.0], &[0.0; 3]);
+
+        assert_eq!(common_grid(&[&a, &b], 5.0), vec![5.0, 10.0, 15.0, 20.0]);
+    }
+
+    #[test]
+    fn common_grid_without_overlap_is_empty() {
+        let a = scan(ScanKind::Sample, &[0.0, 10.0], &[0.0; 2]);
+        let b = scan(ScanKind::Baseline, &[20.0, 30.0], &[0.0; 2]);
+
+        assert!(common_grid(&[&a, &b], 5.0).is_empty());
+        assert!(common_grid(&[&a], 0.0).is_empty());
+    }
+
+    #[test]
+    fn derives_ratio_with_uncertainty() {
+        let p = [0.0, 10.0];
+        let scans = [
+            scan(ScanKind::Baseline, &p, &[10.0, 10.0]),
+            scan(ScanKind::Baseline, &p, &[12.0, 12.0]),
+            scan(ScanKind::Sample, &p, &[5.0, 5.0]),
+            scan(ScanKind::Sample, &p, &[7.0, 7.0]),
+        ];
+
+        let spectrum = derive(&scans, None, 10.0).unwrap();
+
+        // Each signal has a standard error of the mean of 1.
+        let expected = 6.0 / 11.0;
+        let sigma = expected * ((1.0f64 / 6.0).powi(2) + (1.0f64 / 11.0).powi(2)).sqrt();
+        assert_eq!(spectrum.positions, vec![0.0, 10.0]);
+        for j in 0..2 {
+            assert_close(spectrum.values[j], expected);
+            assert_close(spectrum.uncertainties[j], sigma);
+        }
+    }
+
+    #[test]
+    fn derives_dark_corrected_difference() {
+        let p = [0.0, 10.0];
+        let scans = [
+            scan(ScanKind::Dark, &p, &[1.0, 3.0]),
+            scan(ScanKind::Baseline, &p, &[12.0, 12.0]),
+            scan(ScanKind::Sample, &p, &[6.0, 6.0]),
+        ];
+
+        let spectrum = derive(&scans, None, 10.0).unwrap();
+
+        // The dark level is 2 ± sqrt(2) / sqrt(2), so I = 4 ± 1 and I0 = 10 ± 1.
+        let sigma = 0.4 * ((1.0f64 / 4.0).powi(2) + (1.0f64 / 10.0).powi(2)).sqrt();
+        assert_close(spectrum.values[0], 0.4);
+        assert_close(spectrum.uncertainties[0], sigma);
+    }
+
+    #[test]
+    fn derives_normalized_to_reference() {
+        let p = [0.0, 10.0];
+        let sample = [scan(ScanKind::Baseline, &p, &[10.0, 10.0]), scan(ScanKind::Sample, &p, &[4.0, 4.0])];
+        let reference = [scan(ScanKind::Baseline, &p, &[5.0, 5.0]), scan(ScanKind::Sample, &p, &[4.0, 4.0])];
+
+        let spectrum = derive(&sample, Some(&reference), 10.0).unwrap();
+
+        // (4 / 4) / (10 / 5), with no scatter and no dark to add uncertainty.
+        assert_close(spectrum.values[0], 0.5);
+        assert_close(spectrum.uncertainties[0], 0.0);
+    }
+
+    #[test]
+    fn derives_only_where_the_reference_was_recorded() {
+        let sample = [scan(ScanKind::Baseline, &[0.0, 20.0], &[10.0, 10.0]), scan(ScanKind::Sample, &[0.0, 20.0], &[4.0, 4.0])];
+        let reference = [scan(ScanKind::Baseline, &[0.0, 10.0], &[5.0, 5.0]), scan(ScanKind::Sample, &[0.0, 10.0], &[4.0, 4.0])];
+
+        let spectrum = derive(&sample, Some(&reference), 10.0).unwrap();
+
+        assert_eq!(spectrum.positions, vec![0.0, 10.0]);
+        assert!(spectrum.values.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn derive_needs_a_reference_scan_for_each_marked_scan() {
+        let p = [0.0, 10.0];
+        let sample = [scan(ScanKind::Baseline, &p, &[10.0, 10.0]), scan(ScanKind::Sample, &p, &[4.0, 4.0])];
+        let reference = [scan(ScanKind::Baseline, &p, &[5.0, 5.0])];
+
+        assert!(derive(&sample, Some(&reference), 10.0).is_err());
+    }
+
+    #[test]
+    fn derive_needs_marked_overlapping_scans() {
+        let baseline = scan(ScanKind::Baseline, &[0.0, 10.0], &[1.0; 2]);
+        let sample = scan(ScanKind::Sample, &[20.0, 30.0], &[1.0; 2]);
+
+        assert!(derive(std::slice::from_ref(&baseline), None, 1.0).is_err());
+        assert!(derive(std::slice::from_ref(&sample), None, 1.0).is_err());
+        assert!(derive(&[baseline, sample], None, 1.0).is_err());
+    }
+}
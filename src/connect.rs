@@ -0,0 +1,164 @@
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use eframe::egui;
+use serialport::SerialPortInfo;
+
+use crate::drivers::{registry, SettingValue};
+use crate::middleware::{AxisConfig, Detector, DetectorMiddleware, HomeBehavior, MotionControlMiddleware, MotionController};
+use crate::profile;
+
+// Messages from the connection thread to the GUI. Devices are connected one after another, in the order requested.
+pub enum ConnectEvent {
+    Connecting { done: usize, total: usize, device: String }, // Before each device; `done` devices are finished.
+    MotionController(MotionController),
+    Detector(Detector),
+    Failed(String), // One device failed; the others are still connected.
+    Finished,
+}
+
+// Messages from the port probing thread to the GUI.
+pub enum DetectEvent {
+    Probing { done: usize, total: usize, port_name: String },
+    Found(FoundDevice),
+    Finished,
+}
+
+pub struct FoundDevice {
+    pub port_name: String,
+    pub serial_number: Option<String>,
+    pub model: &'static str,
+    pub motion_controller: bool, // Otherwise a detector.
+}
+
+// A device to connect, as selected in the Device Manager.
+pub struct DeviceRequest {
+    pub model: String,
+    pub port_name: String,
+}
+
+/// Connects the requested devices on their own thread, since connecting and homing may take minutes.
+///
+/// Each motion controller is given its calibration, and homed after connecting unless that says otherwise. Each
+/// detector is given its saved settings.
+pub fn start(
+    motion_controllers: Vec<(DeviceRequest, AxisConfig)>,
+    detectors: Vec<(DeviceRequest, Vec<(String, SettingValue)>)>,
+    ctx: egui::Context,
+) -> Receiver<ConnectEvent> {
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let send = |event: ConnectEvent| crate::send_to_gui(&tx, &ctx, event);
+
+        let total = motion_controllers.len() + detectors.len();
+
+        for (i, (req, config)) in motion_controllers.into_iter().enumerate() {
+            let device = format!("{} on {}", req.model, req.port_name);
+            send(ConnectEvent::Connecting { done: i, total, device: device.clone() });
+
+            match connect_motion_controller(req, config) {
+                Ok(mc) => send(ConnectEvent::MotionController(mc)),
+                Err(e) => {
+                    log::error!("Could not connect {}: {}", device, e);
+                    send(ConnectEvent::Failed(format!("{}: {}", device, e)));
+                }
+            }
+        }
+
+        let offset = total - detectors.len();
+        for (i, (req, settings)) in detectors.into_iter().enumerate() {
+            let device = format!("{} on {}", req.model, req.port_name);
+            send(ConnectEvent::Connecting { done: offset + i, total, device: device.clone() });
+
+            match connect_detector(req, settings) {
+                Ok(det) => send(ConnectEvent::Detector(det)),
+                Err(e) => {
+                    log::error!("Could not connect {}: {}", device, e);
+                    send(ConnectEvent::Failed(format!("{}: {}", device, e)));
+                }
+            }
+        }
+
+        send(ConnectEvent::Finished);
+    });
+
+    rx
+}
+
+fn connect_motion_controller(req: DeviceRequest, config: AxisConfig) -> Result<MotionController, String> {
+    let model = registry::motion_control_model(&req.model)
+        .ok_or_else(|| format!("Unknown model \"{}\".", req.model))?;
+
+    let driver = (model.connect)(req.port_name.clone()).map_err(|e| e.to_string())?;
+    let mut mc = MotionController::new(driver, req.port_name);
+    *mc.config_mut() = config;
+    mc.restore_settings().map_err(|e| format!("Applying the settings failed: {}", e))?;
+
+    if mc.config().home != HomeBehavior::Manual {
+        mc.home().map_err(|e| format!("Homing failed: {}", e))?;
+    }
+
+    Ok(mc)
+}
+
+fn connect_detector(req: DeviceRequest, settings: Vec<(String, SettingValue)>) -> Result<Detector, String> {
+    let model = registry::detector_model(&req.model)
+        .ok_or_else(|| format!("Unknown model \"{}\".", req.model))?;
+
+    let driver = (model.connect)(req.port_name.clone()).map_err(|e| e.to_string())?;
+
+    let mut det = Detector::new(driver, req.port_name);
+    det.restore_settings(settings).map_err(|e| format!("Applying the settings failed: {}", e))?;
+
+    Ok(det)
+}
+
+/// Probes each port for each supported model on its own thread, to find out which instrument is on which port.
+///
+/// Ports already in use, e.g. by connected devices, cannot be opened and are quickly passed over.
+pub fn detect(ports: Vec<SerialPortInfo>, ctx: egui::Context) -> Receiver<DetectEvent> {
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let send = |event: DetectEvent| crate::send_to_gui(&tx, &ctx, event);
+
+        let total = ports.len();
+
+        for (done, port) in ports.iter().enumerate() {
+            send(DetectEvent::Probing { done, total, port_name: port.port_name.clone() });
+
+            if let Some(found) = identify(port) {
+                log::info!("Found {} on {}.", found.model, found.port_name);
+                send(DetectEvent::Found(found));
+            }
+        }
+
+        send(DetectEvent::Finished);
+    });
+
+    rx
+}
+
+fn identify(port: &SerialPortInfo) -> Option<FoundDevice> {
+    let found = |model: &'static str, motion_controller: bool| FoundDevice {
+        port_name: port.port_name.clone(),
+        serial_number: profile::usb_serial_number(port),
+        model,
+        motion_controller,
+    };
+
+    for model in registry::MOTION_CONTROL_MODELS {
+        if model.probe.is_some_and(|probe| probe(port.port_name.clone())) {
+            return Some(found(model.name, true));
+        }
+    }
+
+    for model in registry::DETECTOR_MODELS {
+        if model.probe.is_some_and(|probe| probe(port.port_name.clone())) {
+            return Some(found(model.name, false));
+        }
+    }
+
+    None
+}
@@ -0,0 +1,98 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::middleware::Scan;
+
+pub mod archive;
+pub mod delimited;
+pub mod journal;
+
+// Key/value description of how a dataset was taken, written as the header of every data file.
+#[derive(Clone, Default)]
+pub struct Metadata {
+    pub entries: Vec<(String, String)>,
+}
+
+impl Metadata {
+    pub fn push(&mut self, key: impl Into<String>, value: impl ToString) {
+        self.entries.push((key.into(), value.to_string()));
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// Everything recorded by the detectors, as saved to or loaded from a file.
+// Scans are recorded by all detectors at once, so scan k of every detector belongs together.
+#[derive(Clone, Default)]
+pub struct DataSet {
+    pub name: String,
+    pub metadata: Metadata,
+    pub detectors: Vec<String>, // Detector names, one per column.
+    pub scans: Vec<Vec<Scan>>,  // Outer vec is per-detector, inner vec is per-scan.
+    pub sample_ref: Option<(usize, usize)>, // Sample and reference detector indices, if normalized.
+}
+
+/// A file in the temporary directory for a test to write, removed when dropped, so also when the test fails.
+#[cfg(test)]
+pub struct TempFile {
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TempFile {
+    /// Names a new file ending in `extension`, e.g. "csv". Every name is unique, as tests run at once.
+    pub fn new(extension: &str) -> TempFile {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        TempFile {
+            path: std::env::temp_dir().join(format!("mcs_test_{}_{}.{}", std::process::id(), n, extension)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Returns the current UTC time in ISO 8601 format, e.g. "2024-05-01T13:45:00Z".
+pub fn utc_timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's algorithm.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{DataSet, Metadata};
+use crate::analysis::Dark;
+use crate::middleware::{self, Scan, ScanKind};
+
+// Session archives: one self-describing JSON document holding every scan of a session, laid out like a NeXus file.
+// Groups are objects with an `attributes` map; datasets are arrays. Positions are always in nm and readings in pA.
+//
+// Reading one in Python needs nothing beyond the standard library:
+//     data = json.load(open("session.json"))
+//     data["scans"][0]["readings"][data["detectors"][0]["key"]]
+
+const FORMAT: &str = "MCS Session Archive";
+const VERSION: u32 = 2; // 2 keys detectors by their position rather than their name, which need not be unique.
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    format: String,
+    version: u32,
+    attributes: BTreeMap<String, String>, // Device configuration, calibration and scan settings.
+    detectors: Vec<DetectorGroup>,
+    scans: Vec<ScanGroup>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DetectorGroup {
+    #[serde(default)]
+    key: String, // Of its readings and dark level in each scan. Version 1 used the name.
+    name: String,
+    role: String, // "Sample", "Reference" or "None".
+}
+
+impl DetectorGroup {
+    fn key(&self) -> &str {
+        match self.key.is_empty() {
+            true => &self.name,
+            false => &self.key,
+        }
+    }
+}
+
+/// Returns the key of the detector at `idx`.
+fn detector_key(idx: usize) -> String {
+    format!("detector_{}", idx + 1)
+}
+
+#[derive(Serialize, Deserialize)]
+struct DarkGroup {
+    level: f64,
+    uncertainty: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScanGroup {
+    name: String,
+    attributes: BTreeMap<String, String>,
+    positions: Vec<f64>,
+    timestamps: Vec<f64>,
+    readings: BTreeMap<String, Vec<f64>>, // Keyed by detector key.
+    #[serde(default)]
+    settings: BTreeMap<String, BTreeMap<String, String>>, // Of each detector during the scan, keyed by detector key.
+    dark: BTreeMap<String, DarkGroup>,    // Dark level of each detector over the whole session.
+    reference: Option<Vec<f64>>,          // Readings of the reference detector, if one was assigned.
+    normalized: Option<Vec<Option<f64>>>, // Sample / reference. Null where the reference read zero.
+}
+
+/// Writes every scan of `data` to a session archive at `path`.
+pub fn write(path: &str, data: &DataSet) -> Result<(), String> {
+    let role = |i: usize| match data.sample_ref {
+        Some((s, _)) if s == i => "Sample",
+        Some((_, r)) if r == i => "Reference",
+        _ => "None",
+    };
+
+    let darks: Vec<Dark> = data.scans.iter().map(|s| Dark::from_scans(s)).collect();
+    let num_scans = data.scans.first().map_or(0, |s| s.len());
+
+    let scans = (0..num_scans)
+        .map(|k| {
+            let first = &data.scans[0][k];
+
+            let mut attributes = BTreeMap::new();
+            attributes.insert("kind".to_owned(), first.kind.as_str().to_owned());
+            attributes.insert("points".to_owned(), first.positions.len().to_string());
+            attributes.insert("position_units".to_owned(), "nm".to_owned());
+            attributes.insert("reading_units".to_owned(), "pA".to_owned());
+
+            let mut readings = BTreeMap::new();
+            let mut settings = BTreeMap::new();
+            let mut dark = BTreeMap::new();
+            for (i, (det, det_dark)) in data.scans.iter().zip(darks.iter()).enumerate() {
+                readings.insert(detector_key(i), det[k].readings.clone());
+                settings.insert(detector_key(i), det[k].settings.iter().cloned().collect());
+                dark.insert(
+                    detector_key(i),
+                    DarkGroup {
+                        level: det_dark.level,
+                        uncertainty: det_dark.uncertainty,
+                    },
+                );
+            }
+
+            let (reference, normalized) = match data.sample_ref {
+                Some((s, r)) => {
+                    let ratio = middleware::normalize(&data.scans[s][k].readings, &data.scans[r][k].readings);
+                    (
+                        Some(data.scans[r][k].readings.clone()),
+                        Some(ratio.into_iter().map(|v| v.is_finite().then_some(v)).collect()),
+                    )
+                }
+                None => (None, None),
+            };
+
+            ScanGroup {
+                name: format!("scan_{}", k + 1),
+                attributes,
+                positions: first.positions.clone(),
+                timestamps: first.timestamps.clone(),
+                readings,
+                settings,
+                dark,
+                reference,
+                normalized,
+            }
+        })
+        .collect();
+
+    let archive = Archive {
+        format: FORMAT.to_owned(),
+        version: VERSION,
+        attributes: data.metadata.entries.iter().cloned().collect(),
+        detectors: data
+            .detectors
+            .iter()
+            .enumerate()
+            .map(|(i, name)| DetectorGroup {
+                key: detector_key(i),
+                name: name.clone(),
+                role: role(i).to_owned(),
+            })
+            .collect(),
+        scans,
+    };
+
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(file), &archive).map_err(|e| e.to_string())
+}
+
+/// Reads a session archive written by `write`.
+pub fn read(path: &str) -> Result<DataSet, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let archive: Archive =
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| e.to_string())?;
+
+    if archive.format != FORMAT {
+        return Err(format!("Not an {}.", FORMAT));
+    }
+    if archive.version > VERSION {
+        return Err(format!("Archive version {} is newer than this software supports.", archive.version));
+    }
+
+    let detectors: Vec<String> = archive.detectors.iter().map(|d| d.name.clone()).collect();
+    let role_idx = |role: &str| archive.detectors.iter().position(|d| d.role == role);
+
+    let mut scans: Vec<Vec<Scan>> = vec![Vec::new(); detectors.len()];
+    for group in archive.scans.iter() {
+        let kind = group
+            .attributes
+            .get("kind")
+            .and_then(|k| ScanKind::ALL.into_iter().find(|s| s.as_str() == k))
+            .unwrap_or_default();
+
+        for (i, det) in archive.detectors.iter().enumerate() {
+            scans[i].push(Scan {
+                kind,
+                positions: group.positions.clone(),
+                readings: group.readings.get(det.key()).cloned().unwrap_or_default(),
+                timestamps: group.timestamps.clone(),
+                settings: group
+                    .settings
+                    .get(det.key())
+                    .map(|s| s.iter().map(|(l, v)| (l.clone(), v.clone())).collect())
+                    .unwrap_or_default(),
+            });
+        }
+    }
+
+    let mut metadata = Metadata::default();
+    for (key, value) in archive.attributes {
+        metadata.push(key, value);
+    }
+
+    Ok(DataSet {
+        name: std::path::Path::new(path)
+            .file_name()
+            .map_or(path.to_owned(), |n| n.to_string_lossy().to_string()),
+        metadata,
+        detectors,
+        scans,
+        sample_ref: role_idx("Sample").zip(role_idx("Reference")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::TempFile;
+
+    #[test]
+    fn round_trips_detectors_of_the_same_name() {
+        let file = TempFile::new("json");
+        let path = file.as_str();
+
+        let mut metadata = Metadata::default();
+        metadata.push("Operator", "Someone");
+
+        let scan = |kind, readings: &[f64]| Scan {
+            kind,
+            positions: vec![500.0, 510.0],
+            readings: readings.to_vec(),
+            timestamps: vec![1.0, 2.0],
+            settings: vec![("Integration Time [PLC]".to_owned(), readings[0].to_string()), ("Range".to_owned(), "Auto".to_owned())],
+        };
+        let data = DataSet {
+            metadata,
+            detectors: vec!["KI 6485".to_owned(), "KI 6485".to_owned()],
+            scans: vec![
+                vec![scan(ScanKind::Baseline, &[1.0, 2.0]), scan(ScanKind::Sample, &[3.0, 4.0])],
+                vec![scan(ScanKind::Baseline, &[5.0, 6.0]), scan(ScanKind::Sample, &[7.0, 8.0])],
+            ],
+            sample_ref: Some((0, 1)),
+            ..Default::default()
+        };
+
+        write(path, &data).unwrap();
+        let read = read(path).unwrap();
+
+        assert_eq!(read.metadata.get("Operator"), Some("Someone"));
+        assert_eq!(read.detectors, data.detectors);
+        assert_eq!(read.sample_ref, Some((0, 1)));
+        for (det, expected) in read.scans.iter().zip(data.scans.iter()) {
+            assert_eq!(det.len(), expected.len());
+            for (scan, expected) in det.iter().zip(expected.iter()) {
+                assert_eq!(scan.kind, expected.kind);
+                assert_eq!(scan.positions, expected.positions);
+                assert_eq!(scan.readings, expected.readings);
+                assert_eq!(scan.timestamps, expected.timestamps);
+                assert_eq!(scan.settings, expected.settings);
+            }
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::io::{BufRead, Write};
+
+use super::DataSet;
+use crate::middleware::{Scan, ScanKind};
+use crate::units::AxisUnit;
+
+// Delimited text files: a block of `# key: value` metadata lines, one header row, then one row per point.
+// Columns are the scan number, the position and one reading per detector. Files ending in .tsv are tab-separated.
+// Fields holding the delimiter are quoted. A detector without a reading at some point leaves its field empty.
+// The kind of each scan and the settings of each detector during it are kept as `# Scan <k> ...` metadata.
+
+const DETECTOR_UNIT: &str = " [pA]";
+
+fn delimiter(path: &str) -> char {
+    match path.to_lowercase().ends_with(".tsv") {
+        true => '\t',
+        false => ',',
+    }
+}
+
+/// Quotes `field` if it holds the delimiter or a quote, doubling the quotes within.
+fn quote(field: &str, d: char) -> String {
+    match field.contains(d) || field.contains('"') {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
+
+/// Splits a row into its fields, unquoting those in quotes.
+fn split(line: &str, d: char) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            c if c == d && !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields
+}
+
+/// Writes every scan of `data`, with positions converted to `unit`.
+pub fn write(path: &str, data: &DataSet, unit: AxisUnit) -> std::io::Result<()> {
+    let d = delimiter(path);
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    writeln!(file, "# MCS Scan Data")?;
+    for (key, value) in data.metadata.entries.iter() {
+        writeln!(file, "# {}: {}", key, value)?;
+    }
+
+    let scans = data.scans.first().map_or(&[][..], |s| s.as_slice());
+    for (k, scan) in scans.iter().enumerate() {
+        writeln!(file, "# Scan {}: {}", k + 1, scan.kind.as_str())?;
+        for (i, det) in data.scans.iter().enumerate() {
+            for (label, value) in det[k].settings.iter() {
+                writeln!(file, "# Scan {} Detector {} {}: {}", k + 1, i + 1, label, value)?;
+            }
+        }
+    }
+
+    let mut columns = vec!["Scan".to_owned(), unit.axis_label()];
+    for name in data.detectors.iter() {
+        columns.push(format!("{}{}", name, DETECTOR_UNIT));
+    }
+    let columns: Vec<String> = columns.iter().map(|c| quote(c, d)).collect();
+    writeln!(file, "{}", columns.join(&d.to_string()))?;
+
+    for (k, scan) in scans.iter().enumerate() {
+        for j in 0..scan.positions.len() {
+            let mut row = vec![(k + 1).to_string(), unit.nm_to_unit(scan.positions[j]).to_string()];
+            for det in data.scans.iter() {
+                row.push(det[k].readings.get(j).map_or(String::new(), |r| r.to_string()));
+            }
+            writeln!(file, "{}", row.join(&d.to_string()))?;
+        }
+    }
+
+    file.flush()
+}
+
+/// Reads a file written by `write`. Positions are converted back to nm.
+pub fn read(path: &str) -> Result<DataSet, String> {
+    let d = delimiter(path);
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+
+    let mut data = DataSet {
+        name: std::path::Path::new(path)
+            .file_name()
+            .map_or(path.to_owned(), |n| n.to_string_lossy().to_string()),
+        ..Default::default()
+    };
+    let mut unit: Option<AxisUnit> = None;
+
+    for (n, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some((key, value)) = comment.trim().split_once(": ") {
+                data.metadata.push(key, value);
+            }
+            continue;
+        }
+
+        let fields = split(line, d);
+
+        // The first row which is not a comment names the columns.
+        let Some(unit) = unit else {
+            if fields.len() < 3 {
+                return Err(format!("Line {}: expected a scan, position and detector column.", n + 1));
+            }
+
+            unit = Some(
+                AxisUnit::ALL
+                    .into_iter()
+                    .find(|u| u.axis_label() == fields[1].as_str())
+                    .ok_or(format!("Line {}: unknown position column '{}'.", n + 1, fields[1]))?,
+            );
+
+            for name in fields[2..].iter() {
+                data.detectors.push(name.trim_end_matches(DETECTOR_UNIT).to_owned());
+                data.scans.push(Vec::new());
+            }
+            continue;
+        };
+
+        if fields.len() != data.detectors.len() + 2 {
+            return Err(format!("Line {}: expected {} columns.", n + 1, data.detectors.len() + 2));
+        }
+
+        let parse = |s: &str| -> Result<f64, String> {
+            s.trim().parse::<f64>().map_err(|e| format!("Line {}: {}", n + 1, e))
+        };
+
+        let k = parse(&fields[0])? as usize;
+        if k == 0 {
+            return Err(format!("Line {}: scans are numbered from 1.", n + 1));
+        }
+
+        let position = unit.unit_to_nm(parse(&fields[1])?);
+
+        for (i, det) in data.scans.iter_mut().enumerate() {
+            if det.len() < k {
+                det.resize(k, Scan::default());
+            }
+
+            if fields[i + 2].trim().is_empty() {
+                continue;
+            }
+            det[k - 1].positions.push(position);
+            det[k - 1].readings.push(parse(&fields[i + 2])?);
+        }
+    }
+
+    if unit.is_none() {
+        return Err("No data found.".to_owned());
+    }
+
+    // Restore what each scan was marked as, and the settings it was taken with.
+    for (i, det) in data.scans.iter_mut().enumerate() {
+        for (k, scan) in det.iter_mut().enumerate() {
+            if let Some(kind) = data.metadata.get(&format!("Scan {}", k + 1)) {
+                scan.kind = ScanKind::ALL
+                    .into_iter()
+                    .find(|s| s.as_str() == kind)
+                    .unwrap_or_default();
+            }
+
+            let prefix = format!("Scan {} Detector {} ", k + 1, i + 1);
+            scan.settings = data
+                .metadata
+                .entries
+                .iter()
+                .filter_map(|(key, value)| Some((key.strip_prefix(&prefix)?.to_owned(), value.clone())))
+                .collect();
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::{Metadata, TempFile};
+
+    #[test]
+    fn round_trips_metadata_and_columns() {
+        let file = TempFile::new("csv");
+        let path = file.as_str();
+
+        let mut metadata = Metadata::default();
+        metadata.push("Operator", "A. Person, PhD");
+
+        let scan = |kind, positions: &[f64], readings: &[f64]| Scan {
+            kind,
+            positions: positions.to_vec(),
+            readings: readings.to_vec(),
+            settings: vec![("Range".to_owned(), "2 nA".to_owned()), ("Integration Time [PLC]".to_owned(), readings[0].to_string())],
+            ..Scan::default()
+        };
+        let data = DataSet {
+            metadata,
+            detectors: vec!["Sample".to_owned(), "Lamp, \"ref\"".to_owned()],
+            scans: vec![
+                vec![scan(ScanKind::Dark, &[500.0, 510.0], &[1.5, 2.5]), scan(ScanKind::Sample, &[500.0], &[3.0])],
+                vec![scan(ScanKind::Dark, &[500.0], &[-4.0]), scan(ScanKind::Sample, &[500.0], &[5.0])],
+            ],
+            ..Default::default()
+        };
+
+        write(path, &data, AxisUnit::Angstrom).unwrap();
+        let read = read(path).unwrap();
+
+        assert_eq!(read.metadata.get("Operator"), Some("A. Person, PhD"));
+        assert_eq!(read.detectors, data.detectors);
+        assert_eq!(read.scans.len(), 2);
+
+        // The reference has no reading at 510 nm, so it has no point there.
+        for (det, expected) in read.scans.iter().zip(data.scans.iter()) {
+            assert_eq!(det.len(), 2);
+            for (scan, expected) in det.iter().zip(expected.iter()) {
+                assert_eq!(scan.kind, expected.kind);
+                assert_eq!(scan.settings, expected.settings);
+                assert_eq!(scan.readings, expected.readings);
+                assert_eq!(scan.positions.len(), expected.positions.len());
+                for (p, e) in scan.positions.iter().zip(expected.positions.iter()) {
+                    assert!((p - e).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn splits_quoted_fields() {
+        assert_eq!(split("1,\"a, \"\"b\"\"\",,2", ','), vec!["1", "a, \"b\"", "", "2"]);
+        assert_eq!(quote("a, \"b\"", ','), "\"a, \"\"b\"\"\"");
+        assert_eq!(quote("a b", ','), "a b");
+    }
+}
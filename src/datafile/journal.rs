@@ -0,0 +1,258 @@
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use super::DataSet;
+use crate::middleware::{Scan, ScanKind};
+
+// Append-only record of the running session, so that a crash does not lose any scans.
+// Every line is flushed as soon as it is written:
+//
+//     MCS JOURNAL 1
+//     D <name>                              A detector, in connection order. One connected later has empty scans
+//                                           up to then.
+//     S                                     A new scan begins.
+//     K <scan> <kind>                       Scan <scan> (from 1) was marked as <kind>.
+//     P <position> <timestamp> <readings>   A point: nm, Unix seconds, then one reading per detector.
+//     END                                   The session was closed cleanly.
+
+const HEADER: &str = "MCS JOURNAL 1";
+const END: &str = "END";
+
+pub struct Journal {
+    file: std::fs::File,
+}
+
+/// Where the journal of the current session is kept.
+pub fn default_path() -> PathBuf {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(dir) = eframe::storage_dir("MCS") {
+        if std::fs::create_dir_all(&dir).is_ok() {
+            return dir.join("session.journal");
+        }
+    }
+
+    PathBuf::from("session.journal")
+}
+
+impl Journal {
+    /// Starts a new journal, replacing any previous one.
+    pub fn create(path: &PathBuf, detectors: &[String]) -> std::io::Result<Journal> {
+        let mut journal = Journal {
+            file: std::fs::File::create(path)?,
+        };
+
+        journal.line(HEADER)?;
+        for name in detectors {
+            journal.line(&format!("D {}", name))?;
+        }
+
+        Ok(journal)
+    }
+
+    fn line(&mut self, line: &str) -> std::io::Result<()> {
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()
+    }
+
+    /// Adds a detector connected after the journal was started.
+    pub fn detector(&mut self, name: &str) -> std::io::Result<()> {
+        self.line(&format!("D {}", name))
+    }
+
+    pub fn new_scan(&mut self) -> std::io::Result<()> {
+        self.line("S")
+    }
+
+    pub fn scan_kind(&mut self, scan_idx: usize, kind: ScanKind) -> std::io::Result<()> {
+        self.line(&format!("K {} {}", scan_idx + 1, kind.as_str()))
+    }
+
+    pub fn point(&mut self, position: f64, timestamp: f64, readings: &[f64]) -> std::io::Result<()> {
+        let readings: Vec<String> = readings.iter().map(|r| r.to_string()).collect();
+        self.line(&format!("P {} {} {}", position, timestamp, readings.join(" ")))
+    }
+
+    /// Marks the session as closed cleanly, so it is not offered for recovery.
+    pub fn close(mut self) -> std::io::Result<()> {
+        self.line(END)
+    }
+}
+
+/// Recovers the session journaled at `path` as `recover` does, and moves its journal aside, so that the next session
+/// can start its own without overwriting it.
+///
+/// Returns the session, with where its journal now is, to remove once it is saved or discarded. That is None if it
+/// could not be moved, in which case it is left for the next session to replace.
+pub fn set_aside(path: &PathBuf) -> Option<(DataSet, Option<PathBuf>)> {
+    let data = recover(path)?;
+    let aside = path.with_extension("recovered.journal");
+
+    match std::fs::rename(path, &aside) {
+        Ok(_) => Some((data, Some(aside))),
+        Err(e) => {
+            log::error!("Could not move the journal of the last session aside: {}", e);
+            Some((data, None))
+        }
+    }
+}
+
+/// Reads the journal at `path` if its session did not close cleanly and recorded at least one point.
+///
+/// A partially written last line, as left by a crash, is ignored.
+pub fn recover(path: &PathBuf) -> Option<DataSet> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut lines = std::io::BufReader::new(file).lines().map_while(Result::ok);
+
+    if lines.next()? != HEADER {
+        return None;
+    }
+
+    let mut data = DataSet {
+        name: "Recovered Session".to_owned(),
+        ..Default::default()
+    };
+    let mut points = 0;
+
+    for line in lines {
+        let (tag, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+
+        match tag {
+            "D" => {
+                let scans = data.scans.first().map_or(Vec::new(), |s| vec![Scan::default(); s.len()]);
+                data.detectors.push(rest.to_owned());
+                data.scans.push(scans);
+            }
+            "S" => data.scans.iter_mut().for_each(|s| s.push(Scan::default())),
+            "K" => {
+                let Some((idx, kind)) = rest.split_once(' ') else {
+                    continue;
+                };
+                let kind = ScanKind::ALL.into_iter().find(|k| k.as_str() == kind);
+
+                if let (Ok(idx), Some(kind)) = (idx.parse::<usize>(), kind) {
+                    for det in data.scans.iter_mut() {
+                        if let Some(scan) = det.get_mut(idx.wrapping_sub(1)) {
+                            scan.kind = kind;
+                        }
+                    }
+                }
+            }
+            "P" => {
+                let values: Result<Vec<f64>, _> = rest.split(' ').map(|v| v.parse::<f64>()).collect();
+                let Ok(values) = values else {
+                    continue;
+                };
+
+                if values.len() != data.detectors.len() + 2 {
+                    continue;
+                }
+
+                for (i, det) in data.scans.iter_mut().enumerate() {
+                    if let Some(scan) = det.last_mut() {
+                        scan.positions.push(values[0]);
+                        scan.timestamps.push(values[1]);
+                        scan.readings.push(values[i + 2]);
+                    }
+                }
+                points += 1;
+            }
+            END => return None,
+            _ => {}
+        }
+    }
+
+    match points {
+        0 => None,
+        _ => Some(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datafile::TempFile;
+
+    fn record(path: &PathBuf) -> Journal {
+        let mut journal = Journal::create(path, &["Sample".to_owned(), "Reference".to_owned()]).unwrap();
+        journal.new_scan().unwrap();
+        journal.point(500.0, 1.0, &[1.0, 2.0]).unwrap();
+        journal.point(510.0, 2.0, &[3.0, 4.0]).unwrap();
+        journal.scan_kind(0, ScanKind::Baseline).unwrap();
+        journal.new_scan().unwrap();
+        journal.point(500.0, 3.0, &[5.0, 6.0]).unwrap();
+        journal
+    }
+
+    #[test]
+    fn recovers_a_crashed_session_without_its_truncated_last_line() {
+        let file = TempFile::new("journal");
+        let path = &file.path;
+        let mut journal = record(path);
+        write!(journal.file, "P 510 4 7").unwrap(); // Cut off before the second reading.
+        drop(journal);
+
+        let data = recover(path).unwrap();
+
+        assert_eq!(data.detectors, vec!["Sample", "Reference"]);
+        assert_eq!(data.scans[0].len(), 2);
+        assert_eq!(data.scans[0][0].kind, ScanKind::Baseline);
+        assert_eq!(data.scans[0][0].positions, vec![500.0, 510.0]);
+        assert_eq!(data.scans[0][0].timestamps, vec![1.0, 2.0]);
+        assert_eq!(data.scans[1][0].readings, vec![2.0, 4.0]);
+        assert_eq!(data.scans[0][1].kind, ScanKind::Unmarked);
+        assert_eq!(data.scans[0][1].readings, vec![5.0]);
+        assert_eq!(data.scans[1][1].readings, vec![6.0]);
+    }
+
+    #[test]
+    fn gives_a_detector_connected_later_empty_scans_up_to_then() {
+        let file = TempFile::new("journal");
+        let path = &file.path;
+        let mut journal = record(path);
+        journal.detector("Monitor").unwrap();
+        journal.new_scan().unwrap();
+        journal.point(500.0, 4.0, &[7.0, 8.0, 9.0]).unwrap();
+        drop(journal);
+
+        let data = recover(path).unwrap();
+
+        assert_eq!(data.detectors.len(), 3);
+        assert!(data.scans.iter().all(|det| det.len() == 3));
+        assert!(data.scans[2][..2].iter().all(|s| s.readings.is_empty()));
+        assert_eq!(data.scans[2][2].readings, vec![9.0]);
+    }
+
+    #[test]
+    fn sets_aside_the_journal_of_a_crashed_session() {
+        let file = TempFile::new("journal");
+        let path = &file.path;
+        drop(record(path));
+
+        let (data, aside) = set_aside(path).unwrap();
+        let aside = TempFile { path: aside.unwrap() };
+
+        assert!(!path.exists());
+        assert_eq!(data.scans[0].len(), 2);
+        assert_eq!(recover(&aside.path).unwrap().detectors, data.detectors);
+    }
+
+    #[test]
+    fn does_not_recover_a_closed_session() {
+        let file = TempFile::new("journal");
+        let path = &file.path;
+        record(path).close().unwrap();
+
+        assert!(recover(path).is_none());
+    }
+
+    #[test]
+    fn does_not_recover_a_session_without_points() {
+        let file = TempFile::new("journal");
+        let path = &file.path;
+        let mut journal = Journal::create(path, &["Sample".to_owned()]).unwrap();
+        journal.new_scan().unwrap();
+        drop(journal);
+
+        assert!(recover(path).is_none());
+    }
+}
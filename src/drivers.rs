@@ -1,28 +1,159 @@
+use serde::{Deserialize, Serialize};
 use serialport;
 
 pub mod serial;
 pub mod mp_789a_4;
 pub mod ki_6485;
+pub mod registry;
 
 // TODO: Implement custom errors instead of shoe-horning serialport::Error in everywhere.
 // So, we cannot use mutex<()> as some sort of auto-resetting boolean, because thats not how mutexes work and the borrow checkers get angry (rightfully so). Therefore, we need public functions such as "home" that simply set self.homing to true and then call the real, private, do_home() function. Why? Because otherwise if an error propagates, and we are setting the self.homing boolean within the function, it will not be unset (homing forever). This way, if theres an error, we can reset the boolean before propagating the error again.
-pub trait MotionControlDriver {
+pub trait MotionControlDriver: Send {
     fn home(&mut self) -> Result<(), serialport::Error>;
-    fn get_position(&mut self) -> i64;
+    fn get_position(&mut self) -> i64; // Where the driver has commanded the motor to, in steps.
+    fn read_position(&mut self) -> Result<Option<i64>, serialport::Error>; // The controller's own count, if it keeps one.
+    fn limit_status(&mut self) -> Result<LimitStatus, serialport::Error>;
     fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>>;
     fn is_moving(&mut self) -> Result<bool, serialport::Error>;
     fn is_homing(&mut self) -> bool;
     fn move_to(&mut self, position: i64, backlash_correction: i64) -> Result<(), serialport::Error>;
+    fn jog(&mut self, velocity: i64) -> Result<(), serialport::Error>; // Steps per second, signed. Stops and waits when 0.
+    fn capabilities(&mut self) -> MotionCapabilities;
+    fn settings(&mut self) -> Vec<DriverSetting>;
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error>;
     fn short_name(&mut self) -> String;
     fn long_name(&mut self) -> String;
+    fn identification(&mut self) -> String; // As reported by the device when connecting.
+}
+
+// What a motion controller can do, so the GUI only offers what it can.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MotionCapabilities {
+    pub homing: bool,
+    pub encoder: bool,           // `read_position` reads the controller's own count, so lost steps can be told.
+    pub limit_switches: bool,    // `limit_status` reports switches at the ends of travel.
+    pub constant_velocity: bool, // `jog` is supported.
+}
+
+// Which switches the drive is on. Controllers without some switch never report it.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct LimitStatus {
+    pub home: bool, // On the home flag.
+    pub upper: bool,
+    pub lower: bool,
+    pub moving: bool,
+}
+
+impl LimitStatus {
+    /// Whether the drive is on either of the limit switches at the ends of its travel.
+    pub fn at_limit(&self) -> bool {
+        self.upper || self.lower
+    }
+}
+
+// A setting particular to a model of device, such as a speed, by which the GUI can show and change it without knowing
+// the model.
+#[derive(Clone, Debug)]
+pub struct DriverSetting {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: SettingKind,
+    pub value: SettingValue,
+}
+
+impl DriverSetting {
+    /// Returns the name, with the unit of a number, e.g. "Integration Time [PLC]".
+    pub fn label(&self) -> String {
+        match self.kind {
+            SettingKind::Number { unit, .. } if !unit.is_empty() => format!("{} [{}]", self.name, unit),
+            _ => self.name.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SettingKind {
+    Number { unit: &'static str, range: (f64, f64), integer: bool },
+    Toggle,
+    Choice(&'static [&'static str]),
+}
+
+// Saved with the profile, so choices are kept by name rather than position.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Toggle(bool),
+    Number(f64),
+    Choice(String),
+}
+
+impl SettingValue {
+    pub fn as_number(&self, name: &str) -> Result<f64, serialport::Error> {
+        match self {
+            SettingValue::Number(v) => Ok(*v),
+            _ => Err(wrong_value(name, self)),
+        }
+    }
+
+    pub fn as_toggle(&self, name: &str) -> Result<bool, serialport::Error> {
+        match self {
+            SettingValue::Toggle(v) => Ok(*v),
+            _ => Err(wrong_value(name, self)),
+        }
+    }
+
+    /// Returns the index of the choice among `choices`.
+    pub fn as_choice(&self, name: &str, choices: &[&str]) -> Result<usize, serialport::Error> {
+        match self {
+            SettingValue::Choice(v) => choices.iter().position(|c| c == v).ok_or_else(|| wrong_value(name, self)),
+            // Profiles saved before choices were kept by name have their index.
+            SettingValue::Number(i) if i.fract() == 0.0 && (0.0..choices.len() as f64).contains(i) => Ok(*i as usize),
+            _ => Err(wrong_value(name, self)),
+        }
+    }
+}
+
+impl std::fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingValue::Toggle(v) => write!(f, "{}", if *v { "On" } else { "Off" }),
+            SettingValue::Number(v) => write!(f, "{}", v),
+            SettingValue::Choice(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// The error for a setting a driver does not have.
+pub fn unknown_setting(name: &str) -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::InvalidInput, format!("No such setting \"{}\".", name))
+}
+
+fn wrong_value(name: &str, value: &SettingValue) -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::InvalidInput, format!("\"{}\" is not a value of {}.", value, name))
 }
 
 // move_relative is not included in the trait bc the user only ever wants to move to an absolute position, and some controllers have absolute position commands directly. Some do not - only those must implement a relative move function.
 
-pub trait DetectorDriver {
-    fn detect(&mut self) -> Result<f64, serialport::Error>;
+// Detectors report readings beyond their range as this many picoamps or more, after the Keithley convention of 9.9e37 A.
+pub const OVERFLOW: f64 = 9.9e49;
+
+// What a detector can be set to do, so the GUI only offers what it can.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct DetectorCapabilities {
+    pub ranging: bool,         // `range` reports the measurement range in use.
+    pub zero_correction: bool, // `acquire_zero` is supported.
+}
+
+pub trait DetectorDriver: Send {
+    fn detect(&mut self) -> Result<f64, serialport::Error>; // In picoamps.
+    fn range(&mut self) -> String; // The measurement range in use, e.g. "Auto".
+    fn capabilities(&mut self) -> DetectorCapabilities;
+    fn settings(&mut self) -> Vec<DriverSetting>;
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error>;
+    fn acquire_zero(&mut self) -> Result<(), serialport::Error>; // Measures the offset and corrects readings by it.
     fn short_name(&mut self) -> String;
     fn long_name(&mut self) -> String;
+    fn identification(&mut self) -> String; // As reported by the device when connecting.
 }
 
 // pub struct device
@@ -1,12 +1,211 @@
-use super::DetectorDriver;
+use std::time::Duration;
+
+use super::{DetectorCapabilities, DetectorDriver, DriverSetting, SettingKind, SettingValue};
 use crate::drivers::serial::Serial;
 
 const WR_DLY: u64 = 100;
+const SAMPLE_OVERHEAD: f64 = 0.01; // Seconds taken by each sample besides integrating, e.g. to change range.
+const MAX_ERRORS: usize = 10; // Read from the error queue at once, in case it never answers that it is empty.
 const SHORT_NAME: &str = "KI 6485";
 const LONG_NAME: &str = "Keithley Instruments 6485 Picoammeter";
 
+// Setting names, as shown in the GUI and saved with the profile.
+const AUTO_RANGE: &str = "Auto Range";
+const RANGE: &str = "Fixed Range";
+const LOWER_LIMIT: &str = "Auto Range Lower Limit";
+const UPPER_LIMIT: &str = "Auto Range Upper Limit";
+const NPLC: &str = "Integration Time";
+const FILTER: &str = "Averaging Filter";
+const AVERAGING_COUNT: &str = "Averaging Count";
+const MEDIAN: &str = "Median Filter";
+const MEDIAN_RANK: &str = "Median Rank";
+const ZERO_CORRECTION: &str = "Zero Correction";
+
+// The current ranges, by their full scale, and as sent to `RANG`.
+const RANGES: &[&str] = &["2 nA", "20 nA", "200 nA", "2 µA", "20 µA", "200 µA", "2 mA", "20 mA"];
+const RANGE_VALUES: &[&str] = &["2e-9", "2e-8", "2e-7", "2e-6", "2e-5", "2e-4", "2e-3", "2e-2"];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Filter {
+    Off,
+    Repeat, // Each reading is the average of a fresh set of samples.
+    Moving, // Each reading is the average of the latest samples, so readings follow each other faster.
+}
+
+impl Filter {
+    const ALL: [Filter; 3] = [Filter::Off, Filter::Repeat, Filter::Moving];
+    const CHOICES: &'static [&'static str] = &["Off", "Repeat", "Moving"];
+}
+
+const CAPABILITIES: DetectorCapabilities = DetectorCapabilities {
+    ranging: true,
+    zero_correction: true,
+};
+
+// The settings of the picoammeter. Shared with the virtual device, which only keeps them.
+#[derive(Clone)]
+struct Settings {
+    auto_range: bool,
+    range: usize,       // Index into `RANGES`, when not auto ranging.
+    lower_limit: usize, // Index into `RANGES` of the lowest range auto ranging goes to.
+    upper_limit: usize,
+    nplc: f64,           // Integration time, in power line cycles.
+    line_frequency: f64, // Hz, 60 or 50. Integrations are limited to a tenth of a second, so 6 or 5 cycles.
+    filter: Filter,
+    samples: i32,
+    median: bool,
+    median_rank: i32, // The median is of 2 * rank + 1 readings.
+    zero_correction: bool,
+}
+
+impl Settings {
+    const ALL: [&'static str; 10] = [
+        AUTO_RANGE, RANGE, LOWER_LIMIT, UPPER_LIMIT, NPLC, FILTER, AVERAGING_COUNT, MEDIAN, MEDIAN_RANK, ZERO_CORRECTION,
+    ];
+
+    fn new(samples: i32, line_frequency: f64) -> Settings {
+        Settings {
+            auto_range: true,
+            range: 0,
+            lower_limit: 0,
+            upper_limit: RANGES.len() - 1,
+            nplc: line_frequency / 10.0,
+            line_frequency,
+            filter: Filter::Repeat,
+            samples: samples.clamp(2, 100),
+            median: false,
+            median_rank: 1,
+            zero_correction: false,
+        }
+    }
+
+    fn describe(&self) -> Vec<DriverSetting> {
+        let toggle = |name, description, value| DriverSetting {
+            name,
+            description,
+            kind: SettingKind::Toggle,
+            value: SettingValue::Toggle(value),
+        };
+        let range = |name, description, value: usize| DriverSetting {
+            name,
+            description,
+            kind: SettingKind::Choice(RANGES),
+            value: SettingValue::Choice(RANGES[value].to_owned()),
+        };
+        let integer = |name, description, value: i32, range| DriverSetting {
+            name,
+            description,
+            kind: SettingKind::Number { unit: "", range, integer: true },
+            value: SettingValue::Number(value as f64),
+        };
+
+        vec![
+            toggle(AUTO_RANGE, "Picks the range for each reading. Otherwise the fixed range is used.", self.auto_range),
+            range(RANGE, "Full scale when not auto ranging. Readings beyond it overflow.", self.range),
+            range(LOWER_LIMIT, "The most sensitive range auto ranging goes to.", self.lower_limit),
+            range(UPPER_LIMIT, "The least sensitive range auto ranging goes to.", self.upper_limit),
+            DriverSetting {
+                name: NPLC,
+                description: "Of each sample. Longer is quieter but slower.",
+                kind: SettingKind::Number { unit: "PLC", range: (0.01, self.max_nplc()), integer: false },
+                value: SettingValue::Number(self.nplc),
+            },
+            DriverSetting {
+                name: FILTER,
+                description: "Repeat averages a fresh set of samples for each reading. Moving averages the latest, so follows faster.",
+                kind: SettingKind::Choice(Filter::CHOICES),
+                value: SettingValue::Choice(Filter::CHOICES[self.filter as usize].to_owned()),
+            },
+            integer(AVERAGING_COUNT, "Samples averaged into each reading.", self.samples, (2.0, 100.0)),
+            toggle(MEDIAN, "Takes the median of several samples before averaging, to reject spikes.", self.median),
+            integer(MEDIAN_RANK, "The median is of twice this plus one samples.", self.median_rank, (1.0, 5.0)),
+            toggle(ZERO_CORRECTION, "Subtracts the offset measured when the zero was last acquired.", self.zero_correction),
+        ]
+    }
+
+    fn set(&mut self, name: &str, value: &SettingValue) -> Result<(), serialport::Error> {
+        match name {
+            AUTO_RANGE => self.auto_range = value.as_toggle(name)?,
+            RANGE => self.range = value.as_choice(name, RANGES)?,
+            LOWER_LIMIT | UPPER_LIMIT => {
+                let limit = value.as_choice(name, RANGES)?;
+                let (lower, upper) = match name {
+                    LOWER_LIMIT => (limit, self.upper_limit),
+                    _ => (self.lower_limit, limit),
+                };
+                if lower > upper {
+                    return Err(serialport::Error::new(
+                        serialport::ErrorKind::InvalidInput,
+                        "The lower limit of auto ranging must not be above the upper.",
+                    ));
+                }
+                (self.lower_limit, self.upper_limit) = (lower, upper);
+            }
+            NPLC => self.nplc = value.as_number(name)?.clamp(0.01, self.max_nplc()),
+            FILTER => self.filter = Filter::ALL[value.as_choice(name, Filter::CHOICES)?],
+            AVERAGING_COUNT => self.samples = (value.as_number(name)?.round() as i32).clamp(2, 100),
+            MEDIAN => self.median = value.as_toggle(name)?,
+            MEDIAN_RANK => self.median_rank = (value.as_number(name)?.round() as i32).clamp(1, 5),
+            ZERO_CORRECTION => self.zero_correction = value.as_toggle(name)?,
+            _ => return Err(super::unknown_setting(name)),
+        }
+
+        Ok(())
+    }
+
+    /// The longest integration the instrument accepts, a tenth of a second.
+    fn max_nplc(&self) -> f64 {
+        self.line_frequency / 10.0
+    }
+
+    /// Returns the commands which put setting `name` into effect.
+    fn commands(&self, name: &str) -> Vec<String> {
+        let on_off = |on: bool| if on { "ON" } else { "OFF" };
+
+        match name {
+            // Choosing a range turns auto ranging off, so the range is only sent when not auto ranging.
+            AUTO_RANGE | RANGE if self.auto_range => vec!["RANG:AUTO ON".to_owned()],
+            AUTO_RANGE | RANGE => vec![format!("RANG {}", RANGE_VALUES[self.range])],
+            LOWER_LIMIT => vec![format!("RANG:AUTO:LLIM {}", RANGE_VALUES[self.lower_limit])],
+            UPPER_LIMIT => vec![format!("RANG:AUTO:ULIM {}", RANGE_VALUES[self.upper_limit])],
+            NPLC => vec![format!("NPLC {}", self.nplc)],
+            FILTER => match self.filter {
+                Filter::Off => vec!["AVER OFF".to_owned()],
+                Filter::Repeat => vec!["AVER:TCON REP".to_owned(), "AVER ON".to_owned()],
+                Filter::Moving => vec!["AVER:TCON MOV".to_owned(), "AVER ON".to_owned()],
+            },
+            AVERAGING_COUNT => vec![format!("AVER:COUN {}", self.samples)],
+            MEDIAN => vec![format!("MED {}", on_off(self.median))],
+            MEDIAN_RANK => vec![format!("MED:RANK {}", self.median_rank)],
+            ZERO_CORRECTION => vec![format!("SYST:ZCOR {}", on_off(self.zero_correction))],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The longest a reading may take, with room to spare, as every sample of the filters integrates in turn.
+    fn reading_time(&self) -> Duration {
+        let averaged = match self.filter {
+            Filter::Off => 1,
+            Filter::Repeat | Filter::Moving => self.samples, // A moving average fills up once first.
+        };
+        let median = if self.median { 2 * self.median_rank + 1 } else { 1 };
+        let sample = self.nplc / self.line_frequency + SAMPLE_OVERHEAD;
+
+        Duration::from_secs_f64(1.0 + 2.0 * (averaged * median) as f64 * sample)
+    }
+
+    fn range(&self) -> String {
+        match self.auto_range {
+            true => "Auto".to_owned(),
+            false => RANGES[self.range].to_owned(),
+        }
+    }
+}
+
 pub struct Ki6485 {
     comms: Serial,
+    identification: String,
+    settings: Settings,
 }
 
 // Public functions.
@@ -25,6 +224,8 @@ impl Ki6485 {
         comms.xfer_sleep(b"*RST\r", 400)?;
         comms.xfer(b"*IDN?\r")?;
 
+        let identification = comms.recv_string();
+
         if comms.recv_contains(b"KEITHLEY INSTRUMENTS INC.,MODEL 6485") {
             log::info!("Connected to {} at port {}.", SHORT_NAME, port_name);
         } else {
@@ -35,63 +236,156 @@ impl Ki6485 {
             ));
         }
 
-        // Set up device.
-        // Set up and start up command sequence for the KI 6485.
-        comms.xfer(b"SYST:ZCH ON\r")?;
-        comms.xfer(b"RANG 2e-9\r")?;
-        comms.xfer(b"INIT\r")?;
-        comms.xfer(b"SYST:ZCOR:ACQ\r")?; // acquire zero current
-        comms.xfer(b"SYST:ZCOR ON\r")?; // perform zero correction
-        comms.xfer(b"RANG:AUTO ON\r")?; // enable auto range
-        comms.xfer(b"SYST:ZCH OFF\r")?; // disable zero check
-        comms.xfer(b"SYST:ZCOR OFF\r")?; // disable zero correction
-        comms.xfer(b"AVER ON\r")?;
-        comms.xfer(b"AVER:TCON REP\r")?;
-        comms.xfer(format!("AVER:COUN {}\r", samples).as_bytes())?; // enable averaging
+        // Integration times are in line cycles. Taken to be 60 Hz if the answer is unclear.
+        let line_frequency = comms
+            .xfer(b"SYST:LFR?\r")
+            .ok()
+            .and_then(|_| comms.recv_string().parse::<f64>().ok())
+            .filter(|f| *f == 50.0 || *f == 60.0)
+            .unwrap_or(60.0);
+
+        let mut ki = Ki6485 {
+            comms,
+            identification,
+            settings: Settings::new(samples, line_frequency),
+        };
+
+        // Errors from before the reset are of no concern.
+        let _ = ki.check_errors();
+
+        // Set up device. A zero is acquired for when correction is turned on, but not used until then.
+        ki.acquire_offset()?;
+        for name in Settings::ALL {
+            ki.apply(name)?;
+        }
 
         log::debug!("Init complete");
 
-        Ok(Ki6485 { comms })
+        Ok(ki)
+    }
+
+    /// Checks whether a 6485 answers on the port, by its identification alone. Unlike `new` it does not reset the device.
+    pub fn probe(port_name: String) -> bool {
+        let Ok(mut comms) = Serial::new(port_name, WR_DLY) else {
+            return false;
+        };
+
+        comms.xfer(b"*IDN?\r").is_ok() && comms.recv_contains(b"KEITHLEY INSTRUMENTS INC.,MODEL 6485")
+    }
+}
+
+// Private functions.
+impl Ki6485 {
+    /// Sends the commands which put setting `name` into effect, failing if the instrument rejects any of them.
+    fn apply(&mut self, name: &str) -> Result<(), serialport::Error> {
+        for command in self.settings.commands(name) {
+            self.comms.xfer(format!("{}\r", command).as_bytes())?;
+        }
+        self.check_errors()
     }
 
-    // Not applicable to all detectors so not part of the interface.
-    pub fn set_samples(&mut self, mut samples: i32) -> Result<(), serialport::Error> {
-        // Set samples between 2 and 20
-        if samples < 2 {
-            samples = 2;
+    /// Empties the error queue, failing with its errors if it held any.
+    ///
+    /// The instrument answers a command it rejects like any other, so this is the only way to tell.
+    fn check_errors(&mut self) -> Result<(), serialport::Error> {
+        let mut errors = Vec::new();
+
+        for _ in 0..MAX_ERRORS {
+            self.comms.xfer(b"SYST:ERR?\r")?;
+
+            // Expected format: code,"message". Code 0 once the queue is empty.
+            let msg = self.comms.recv_string();
+            match msg.split_once(',').and_then(|(code, _)| code.trim().parse::<i32>().ok()) {
+                Some(0) | None => break,
+                Some(_) => errors.push(msg),
+            }
         }
-        else if samples > 20 {
-            samples = 20;
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(serialport::Error::new(
+                serialport::ErrorKind::InvalidInput,
+                format!("{} reported: {}", SHORT_NAME, errors.join("; ")),
+            )),
         }
+    }
 
-        self.comms.xfer(format!("AVER:COUN {}\r", samples).as_bytes())?;
-        Ok(())
+    /// Measures the offset with the input shunted by zero check, on the fixed range or else the lowest auto ranging
+    /// goes to, and leaves the range as it was.
+    fn acquire_offset(&mut self) -> Result<(), serialport::Error> {
+        let range = match self.settings.auto_range {
+            true => self.settings.lower_limit,
+            false => self.settings.range,
+        };
+
+        self.comms.xfer(b"SYST:ZCH ON\r")?; // enable zero check
+        self.comms.xfer(format!("RANG {}\r", RANGE_VALUES[range]).as_bytes())?;
+        self.comms.xfer(b"INIT\r")?;
+        self.comms.xfer(b"SYST:ZCOR:ACQ\r")?; // acquire zero current
+        self.comms.xfer(b"SYST:ZCH OFF\r")?; // disable zero check
+        self.apply(AUTO_RANGE)
     }
 }
 
 // Public interface.
 impl DetectorDriver for Ki6485 {
     fn detect(&mut self) -> Result<f64, serialport::Error> {
-        self.comms.xfer(b"READ?\r")?;
-        
-        // Probably a better way to do this.
+        self.comms.xfer_wait(b"READ?\r", self.settings.reading_time())?;
+
         // Expected format:
         // MeasurementA,Timestamp,Error
-        let mut recv = self.comms.get_recv();
-        let mut msg = String::from_utf8(recv.to_vec()).unwrap();
-        
-        let mut words = msg.split(",");
-        
-        let mut value = words.next().unwrap().chars();
-        let timestamp = words.next().unwrap();
-        let error = words.next().unwrap();
-
-        value.next_back();
-        let mes = value.as_str().parse::<f64>().unwrap();
+        let msg = self.comms.recv_string();
+        let mes = msg
+            .split(',')
+            .next()
+            .map(|v| v.trim_end_matches('A'))
+            .and_then(|v| v.parse::<f64>().ok())
+            .ok_or_else(|| {
+                serialport::Error::new(
+                    serialport::ErrorKind::InvalidInput,
+                    format!("Unexpected reading from {}: {:?}", SHORT_NAME, msg),
+                )
+            })?;
 
         Ok(mes * 1e12) // Convert from amps to picoamps
     }
 
+    fn range(&mut self) -> String {
+        self.settings.range()
+    }
+
+    fn capabilities(&mut self) -> DetectorCapabilities {
+        CAPABILITIES
+    }
+
+    fn settings(&mut self) -> Vec<DriverSetting> {
+        self.settings.describe()
+    }
+
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error> {
+        let previous = self.settings.clone();
+        self.settings.set(name, &value)?;
+
+        // Rejected, the previous value is put back, so that what is reported is what is in effect.
+        if let Err(e) = self.apply(name) {
+            self.settings = previous;
+            let _ = self.apply(name);
+            return Err(e);
+        }
+
+        log::info!("{} of {} set to {}.", name, SHORT_NAME, value);
+        Ok(())
+    }
+
+    fn acquire_zero(&mut self) -> Result<(), serialport::Error> {
+        self.acquire_offset()?;
+        self.settings.zero_correction = true;
+        self.apply(ZERO_CORRECTION)?;
+
+        log::info!("Acquired the zero of {}.", SHORT_NAME);
+        Ok(())
+    }
+
     fn short_name(&mut self) -> String {
         SHORT_NAME.to_string()
     }
@@ -99,6 +393,10 @@ impl DetectorDriver for Ki6485 {
     fn long_name(&mut self) -> String {
         LONG_NAME.to_string()
     }
+
+    fn identification(&mut self) -> String {
+        self.identification.clone()
+    }
 }
 
 //
@@ -107,12 +405,14 @@ impl DetectorDriver for Ki6485 {
 //
 
 pub struct Ki6485Virtual {
-    port_name: String,
+    settings: Settings,
 }
 
 impl Ki6485Virtual {
-    pub fn new(port_name: String, samples: i32) -> Ki6485Virtual {
-        Ki6485Virtual { port_name }
+    pub fn new(_port_name: String, samples: i32) -> Ki6485Virtual {
+        Ki6485Virtual {
+            settings: Settings::new(samples, 60.0),
+        }
     }
 }
 
@@ -121,6 +421,27 @@ impl DetectorDriver for Ki6485Virtual {
         Ok(rand::random::<f64>())
     }
 
+    fn range(&mut self) -> String {
+        self.settings.range()
+    }
+
+    fn capabilities(&mut self) -> DetectorCapabilities {
+        CAPABILITIES
+    }
+
+    fn settings(&mut self) -> Vec<DriverSetting> {
+        self.settings.describe()
+    }
+
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error> {
+        self.settings.set(name, &value)
+    }
+
+    fn acquire_zero(&mut self) -> Result<(), serialport::Error> {
+        self.settings.zero_correction = true;
+        Ok(())
+    }
+
     fn short_name(&mut self) -> String {
         SHORT_NAME.to_string()
     }
@@ -128,4 +449,43 @@ impl DetectorDriver for Ki6485Virtual {
     fn long_name(&mut self) -> String {
         LONG_NAME.to_string()
     }
+
+    fn identification(&mut self) -> String {
+        format!("Virtual {}", SHORT_NAME)
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_integrations_to_a_tenth_of_a_second() {
+        let mut settings = Settings::new(10, 50.0);
+        assert_eq!(settings.nplc, 5.0);
+
+        settings.set(NPLC, &SettingValue::Number(50.0)).unwrap();
+        assert_eq!(settings.nplc, 5.0);
+    }
+
+    #[test]
+    fn keeps_the_previous_value_of_a_rejected_setting() {
+        // Rejects any averaging count but 10.
+        let mut rejected = false;
+        let (comms, _) = Serial::scripted(move |command| match command.trim_end() {
+            "SYST:ERR?" if std::mem::take(&mut rejected) => "-222,\"Parameter data out of range\"\r\n".to_owned(),
+            "SYST:ERR?" => "0,\"No error\"\r\n".to_owned(),
+            c => {
+                rejected = c.starts_with("AVER:COUN") && c != "AVER:COUN 10";
+                "\r\n".to_owned()
+            }
+        });
+        let mut ki = Ki6485 {
+            comms,
+            identification: String::new(),
+            settings: Settings::new(10, 60.0),
+        };
+
+        assert!(ki.set_setting(AVERAGING_COUNT, SettingValue::Number(20.0)).is_err());
+        assert_eq!(ki.settings.samples, 10);
+    }
 }
@@ -1,18 +1,149 @@
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::MotionControlDriver;
+use super::{DriverSetting, LimitStatus, MotionCapabilities, MotionControlDriver, SettingKind, SettingValue};
 use crate::drivers::serial::Serial;
 
 const WR_DLY: u64 = 50; // milliseconds
 const SHORT_NAME: &str = "MP 789A-4";
 const LONG_NAME: &str = "McPherson 789A-4";
 
+// Names of the settings.
+const VELOCITY: &str = "Velocity";
+const ACCELERATION: &str = "Acceleration";
+const HOME_MODE: &str = "Homing Mode";
+const HOME_VELOCITY: &str = "Homing Velocity";
+const HOME_BACK_OFF: &str = "Homing Back Off";
+const HOME_RETURN: &str = "Homing Return";
+const EDGE_VELOCITY: &str = "Edge Find Velocity";
+const LIMIT_BACK_OFF: &str = "Limit Back Off";
+
+const NO_HOME_FLAG: &str = "Hit edge limit switch when homing. Does this device have a home sensor? If not, home it on a limit switch instead.";
+
 pub struct Mp789a4 {
     comms: Serial,
+    identification: String,
     position: i64,
     moving: bool,
     homing: bool,
+    jog: Option<(Instant, i64)>, // When the constant velocity move began, and at how many steps per second.
+    origin: Option<i64>, // The controller's position counter where `position` is 0, if it answers with one.
+    settings: Settings,
+}
+
+// What is homed on. Older drives have no home flag, and are homed a set distance off one of their limit switches.
+#[derive(Clone, Copy, PartialEq)]
+enum HomeMode {
+    Flag,
+    LowerLimit,
+    UpperLimit,
+}
+
+impl HomeMode {
+    const ALL: [HomeMode; 3] = [HomeMode::Flag, HomeMode::LowerLimit, HomeMode::UpperLimit];
+    const CHOICES: &'static [&'static str] = &["Home Flag", "Lower Limit", "Upper Limit"];
+}
+
+// Speeds in steps per second, distances in steps.
+struct Settings {
+    velocity: i64,     // Of moves. 0 sends nothing, leaving the current value, as does `acceleration`.
+    acceleration: i64,
+    home_mode: HomeMode,
+    home_velocity: i64, // Seeking the home flag or limit switch.
+    home_back_off: i64, // Back into the home flag, after overshooting it.
+    home_return: i64,   // Up again, to find its edge from.
+    edge_velocity: i64, // Finding the edge of the home flag, for accuracy.
+    limit_back_off: i64, // Away from the limit switch homed on, to where home is.
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            velocity: 0,
+            acceleration: 0,
+            home_mode: HomeMode::Flag,
+            home_velocity: 23000,
+            home_back_off: 108000,
+            home_return: 72000,
+            edge_velocity: 1000,
+            limit_back_off: 10000,
+        }
+    }
+}
+
+impl Settings {
+    fn describe(&self) -> Vec<DriverSetting> {
+        let number = |name, description, unit, value: i64, range| DriverSetting {
+            name,
+            description,
+            kind: SettingKind::Number { unit, range, integer: true },
+            value: SettingValue::Number(value as f64),
+        };
+
+        vec![
+            number(VELOCITY, "Of moves. 0 leaves the current value unchanged.", "steps/s", self.velocity, (0.0, 50000.0)),
+            number(ACCELERATION, "Of moves. 0 leaves the current value unchanged.", "steps/s²", self.acceleration, (0.0, 1000000.0)),
+            DriverSetting {
+                name: HOME_MODE,
+                description: "Drives without a home flag are homed off a limit switch.",
+                kind: SettingKind::Choice(HomeMode::CHOICES),
+                value: SettingValue::Choice(HomeMode::CHOICES[self.home_mode as usize].to_owned()),
+            },
+            number(HOME_VELOCITY, "Seeking the home flag or limit switch.", "steps/s", self.home_velocity, (100.0, 50000.0)),
+            number(HOME_BACK_OFF, "Back into the home flag after finding it.", "steps", self.home_back_off, (0.0, 1000000.0)),
+            number(HOME_RETURN, "Up again, to approach its edge from.", "steps", self.home_return, (0.0, 1000000.0)),
+            number(EDGE_VELOCITY, "Approaching the edge of the home flag. Slower is more accurate.", "steps/s", self.edge_velocity, (10.0, 10000.0)),
+            number(LIMIT_BACK_OFF, "Away from the limit switch homed on, to where home is. Must clear the switch.", "steps", self.limit_back_off, (1.0, 1000000.0)),
+        ]
+    }
+
+    fn set(&mut self, name: &str, value: &SettingValue) -> Result<(), serialport::Error> {
+        if name == HOME_MODE {
+            self.home_mode = HomeMode::ALL[value.as_choice(name, HomeMode::CHOICES)?];
+            return Ok(());
+        }
+
+        let field = match name {
+            VELOCITY => &mut self.velocity,
+            ACCELERATION => &mut self.acceleration,
+            HOME_VELOCITY => &mut self.home_velocity,
+            HOME_BACK_OFF => &mut self.home_back_off,
+            HOME_RETURN => &mut self.home_return,
+            EDGE_VELOCITY => &mut self.edge_velocity,
+            LIMIT_BACK_OFF => &mut self.limit_back_off,
+            _ => return Err(super::unknown_setting(name)),
+        };
+        *field = value.as_number(name)?.round() as i64;
+
+        // Left on the switch, any move ending at home would count as running into the limit.
+        if name == LIMIT_BACK_OFF {
+            self.limit_back_off = self.limit_back_off.max(1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes the answer to `]`, the limit switch status.
+///
+/// The status is a sum of flags: 2 while moving, 32 on the home flag, 64 on the upper and 128 on the lower limit switch.
+/// So 0 and 2 are off every switch, 32 and 34 on the home flag, stationary or moving. An answer with a + or - in it is
+/// the echo of a move, not a status.
+fn decode_limits(msg: &str) -> Option<LimitStatus> {
+    if msg.contains(['+', '-']) {
+        return None;
+    }
+
+    let code = msg
+        .split(|c: char| !c.is_ascii_digit())
+        .find_map(|v| v.parse::<u8>().ok())?;
+
+    Some(LimitStatus {
+        moving: code & 2 != 0,
+        home: code & 32 != 0,
+        upper: code & 64 != 0,
+        lower: code & 128 != 0,
+    })
 }
 
 // Public functions.
@@ -29,6 +160,7 @@ impl Mp789a4 {
 
         // Request identification.
         comms.xfer(b" \r")?;
+        let identification = comms.recv_string();
 
         if comms.recv_contains(b" v2.55\r\n#\r\n") {
             log::info!(
@@ -46,16 +178,67 @@ impl Mp789a4 {
             ));
         }
 
-        let mut dev = Mp789a4 {
+        // Homing is left to the middleware, which knows whether it is wanted right now.
+        let mut mp = Mp789a4 {
             comms,
+            identification,
             position: 0,
             moving: false,
             homing: false,
+            jog: None,
+            origin: None,
+            settings: Settings::default(),
         };
+        mp.origin = mp.read_counter()?;
+        if mp.origin.is_none() {
+            log::warn!("{} on port {} does not report its position. Lost steps will go unnoticed.", SHORT_NAME, port_name);
+        }
+
+        Ok(mp)
+    }
+
+    /// Checks whether a 789A-4 answers on the port, by the same handshake as `new` but without changing its state.
+    pub fn probe(port_name: String) -> bool {
+        let Ok(mut comms) = Serial::new(port_name, WR_DLY) else {
+            return false;
+        };
+
+        comms.xfer(b" \r").is_ok() && (comms.recv_contains(b" v2.55\r\n#\r\n") || comms.recv_contains(b" #\r\n"))
+    }
 
-        dev.home()?;
+    /// Asks for the limit switch status, returning None if the answer is not one.
+    fn poll_limits(&mut self) -> Result<Option<LimitStatus>, serialport::Error> {
+        self.comms.xfer(b"]\r")?;
+        Ok(decode_limits(&self.comms.recv_string()))
+    }
+
+    /// Asks the controller whether the motor is moving.
+    ///
+    /// Unlike `is_moving`, this ignores `self.moving`, which is set for the whole of `move_to` and so cannot tell the
+    /// waits within it when their move has ended.
+    fn poll_moving(&mut self) -> Result<bool, serialport::Error> {
+        self.comms.xfer(b"^\r")?;
+
+        // An answer with a + or - in it is the echo of a move, not a status. Without a clear answer, assume it moves.
+        let stopped = self.comms.recv_contains(b"0") && !self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-");
+        if stopped {
+            self.moving = false;
+        }
 
-        Ok(dev)
+        Ok(!stopped)
+    }
+
+    /// Reads the position counter of the controller, which counts the steps actually sent to the motor.
+    ///
+    /// Returns None if the answer holds no number, as from firmware without the command.
+    fn read_counter(&mut self) -> Result<Option<i64>, serialport::Error> {
+        self.comms.xfer(b"Z\r")?;
+
+        // Expected format: the echoed command, then the count.
+        let msg = self.comms.recv_string();
+        Ok(msg
+            .split_whitespace()
+            .find_map(|v| v.trim_start_matches('Z').parse::<i64>().ok()))
     }
 
     fn move_relative(&mut self, steps: i64) -> Result<(), serialport::Error> {
@@ -71,7 +254,7 @@ impl Mp789a4 {
             }
         }
 
-        while self.is_moving()? {
+        while self.poll_moving()? {
             log::debug!("Blocking until movement completes.");
             sleep(Duration::from_millis(500));
         }
@@ -87,45 +270,38 @@ impl Mp789a4 {
 // Private functions.
 impl Mp789a4 {
     fn _home(&mut self) -> Result<(), serialport::Error> {
+        if self.settings.home_mode != HomeMode::Flag {
+            return self._home_on_limit();
+        }
+
         log::info!("Homing MP789A4.");
 
         // Enable the 789A-4's homing circuit.
         self.comms.xfer(b"A1\r")?;
 
         // Check limit switch status.
-        self.comms.xfer(b"]\r")?;
+        let status = self.poll_limits()?;
 
         // Carries out the 789A-4 homing algorithm as described in the manual.
-        if self.comms.recv_contains(b"32")
-            && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-        {
+        if status.is_some_and(|s| s.home && !s.moving) {
             log::info!("Homing switch blocked.");
 
             // Home switch blocked.
-            // Move at constant velocity (23 kHz).
-            self.comms.xfer(b"M+23000\r")?;
+            // Move at constant velocity (23 kHz by default).
+            self.comms.xfer(format!("M+{}\r", self.settings.home_velocity).as_bytes())?;
 
             loop {
                 // Check limit status every 0.8 seconds.
-                self.comms.xfer(b"]\r")?;
+                let status = self.poll_limits()?;
 
-                if (self.comms.recv_contains(b"0") || self.comms.recv_contains(b"2"))
-                    && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-                {
-                    // Not-on-a-limit-switch status is 0 when stationary, 2 when in motion.
+                if status.is_some_and(|s| !s.home && !s.at_limit()) {
+                    // Off the home flag.
                     break;
-                } else if (self.comms.recv_contains(b"64") || self.comms.recv_contains(b"128"))
-                    && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-                {
+                } else if status.is_some_and(|s| s.at_limit()) {
                     // If we have hit either of the extreme limit switches and stopped.
-                    log::error!(
-                        "Hit edge limit switch when homing. Does this device have a home sensor?"
-                    );
+                    log::error!("{}", NO_HOME_FLAG);
                     // TODO: Should NOT be a serialport error.
-                    return Err(serialport::Error::new(
-                        serialport::ErrorKind::InvalidInput,
-                        "Hit edge limit switch when homing. Does this device have a home sensor?",
-                    ));
+                    return Err(serialport::Error::new(serialport::ErrorKind::InvalidInput, NO_HOME_FLAG));
                 }
 
                 sleep(Duration::from_millis(800));
@@ -134,47 +310,35 @@ impl Mp789a4 {
             // Soft stop when homing flag is located.
             self.comms.xfer(b"@\r")?;
 
-            // Back into home switch 3 motor revolutions.
-            self.comms.xfer(b"-108000\r")?;
-            // Go 2 motor revolutions up.
-            self.comms.xfer(b"+72000\r")?;
+            // Back into home switch, by default 3 motor revolutions.
+            self.comms.xfer(format!("-{}\r", self.settings.home_back_off).as_bytes())?;
+            // Go up, by default 2 motor revolutions.
+            self.comms.xfer(format!("+{}\r", self.settings.home_return).as_bytes())?;
             // Enable 'high accuracy' circuit.
             self.comms.xfer(b"A24\r")?;
 
-            // Find edge of home flag at 1000 steps/sec.
-            self.comms.xfer_sleep(b"F1000,0\r", WR_DLY * 7)?;
+            // Find edge of home flag, by default at 1000 steps/sec.
+            self.comms.xfer_sleep(format!("F{},0\r", self.settings.edge_velocity).as_bytes(), WR_DLY * 7)?;
 
             // Disable home circuit.
             self.comms.xfer(b"A0\r")?;
-        } else if self.comms.recv_contains(b"0")
-            && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-        {
+        } else if status == Some(LimitStatus::default()) {
             // Home switch not blocked.
-            // Move at constant velocity (23 kHz).
-            self.comms.xfer(b"M-23000\r")?;
+            // Move at constant velocity (23 kHz by default).
+            self.comms.xfer(format!("M-{}\r", self.settings.home_velocity).as_bytes())?;
 
             loop {
                 // Check limit status every 0.8 seconds.
-                self.comms.xfer(b"]\r")?;
+                let status = self.poll_limits()?;
 
-                if (self.comms.recv_contains(b"32") || self.comms.recv_contains(b"34"))
-                    && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-                {
-                    // Home-switch-blocked status is 32 when stationary, 34 when in motion.
+                if status.is_some_and(|s| s.home) {
+                    // On the home flag.
                     break;
-                } else if (self.comms.recv_contains(b"64") || self.comms.recv_contains(b"128"))
-                    && (!self.comms.recv_contains(b"+") && !self.comms.recv_contains(b"-"))
-                {
+                } else if status.is_some_and(|s| s.at_limit()) {
                     // If we have hit either of the extreme limit switches and stopped.
-                    // TODO: Some 789s don't have a limit switch. In this case, we will need to home using the lower limit switch... ?
-                    log::error!(
-                        "Hit edge limit switch when homing. Does this device have a home sensor?"
-                    );
+                    log::error!("{}", NO_HOME_FLAG);
                     // TODO: Should NOT be a serialport error.
-                    return Err(serialport::Error::new(
-                        serialport::ErrorKind::InvalidInput,
-                        "Hit edge limit switch when homing. Does this device have a home sensor?",
-                    ));
+                    return Err(serialport::Error::new(serialport::ErrorKind::InvalidInput, NO_HOME_FLAG));
                 }
 
                 sleep(Duration::from_millis(800));
@@ -183,15 +347,15 @@ impl Mp789a4 {
             // Soft stop when homing flag is located.
             self.comms.xfer(b"@\r")?;
 
-            // Back into home switch 3 motor revolutions.
-            self.comms.xfer(b"-108000\r")?;
-            // Go 2 motor revolutions up.
-            self.comms.xfer(b"+72000\r")?;
+            // Back into home switch, by default 3 motor revolutions.
+            self.comms.xfer(format!("-{}\r", self.settings.home_back_off).as_bytes())?;
+            // Go up, by default 2 motor revolutions.
+            self.comms.xfer(format!("+{}\r", self.settings.home_return).as_bytes())?;
             // Enable 'high accuracy' circuit.
             self.comms.xfer(b"A24\r")?;
 
-            // Find edge of home flag at 1000 steps/sec.
-            self.comms.xfer_sleep(b"F1000,0\r", WR_DLY * 7)?;
+            // Find edge of home flag, by default at 1000 steps/sec.
+            self.comms.xfer_sleep(format!("F{},0\r", self.settings.edge_velocity).as_bytes(), WR_DLY * 7)?;
 
             // Disable home circuit.
             self.comms.xfer(b"A0\r")?;
@@ -205,13 +369,13 @@ impl Mp789a4 {
 
         // The standard is for the device drivers to read 0 when homed if the controller does not itself provide a value.
         // It is up to the middleware to handle zero- and home-offsets.
-        if self.is_moving()? {
+        if self.poll_moving()? {
             log::warn!("Post-home movement detected. Entering movement remediation.");
             self.comms.xfer_sleep(b"@\r", WR_DLY * 10)?;
         }
 
         let mut stop_attempts = 0;
-        while self.is_moving()? {
+        while self.poll_moving()? {
             if stop_attempts > 3 {
                 stop_attempts = 1;
                 log::warn!("Re-commanding that device ceases movement.");
@@ -223,6 +387,56 @@ impl Mp789a4 {
         }
 
         self.position = 0;
+        self.origin = self.read_counter()?;
+
+        Ok(())
+    }
+
+    /// Homes a drive without a home flag, by seeking the chosen limit switch and backing off it to where home is.
+    fn _home_on_limit(&mut self) -> Result<(), serialport::Error> {
+        let lower = self.settings.home_mode == HomeMode::LowerLimit;
+        log::info!("Homing MP789A4 on its {} limit switch.", if lower { "lower" } else { "upper" });
+
+        // Move at constant velocity until the limit switch stops the drive.
+        let direction = if lower { '-' } else { '+' };
+        self.comms.xfer(format!("M{}{}\r", direction, self.settings.home_velocity).as_bytes())?;
+
+        loop {
+            sleep(Duration::from_millis(800));
+
+            // Check limit status every 0.8 seconds.
+            let status = self.poll_limits()?;
+
+            if status.is_some_and(|s| if lower { s.lower } else { s.upper }) {
+                break;
+            } else if status.is_some_and(|s| s.at_limit()) {
+                return Err(serialport::Error::new(
+                    serialport::ErrorKind::InvalidInput,
+                    "Hit the opposite limit switch when homing. Are the limit switches swapped?",
+                ));
+            }
+        }
+
+        // Soft stop, should the switch not have stopped it already.
+        self.comms.xfer(b"@\r")?;
+        while self.poll_moving()? {
+            log::debug!("Waiting for device to cease movement.");
+            sleep(Duration::from_millis(500));
+        }
+
+        // Back off the limit switch, away from it.
+        let back_off = self.settings.limit_back_off;
+        self.move_relative(if lower { back_off } else { -back_off })?;
+
+        if self.poll_limits()?.is_some_and(|s| s.at_limit()) {
+            return Err(serialport::Error::new(
+                serialport::ErrorKind::InvalidInput,
+                "Still on the limit switch after backing off. Increase the Limit Back Off.",
+            ));
+        }
+
+        self.position = 0;
+        self.origin = self.read_counter()?;
 
         Ok(())
     }
@@ -247,6 +461,39 @@ impl Mp789a4 {
     }
 }
 
+#[cfg(test)]
+impl Mp789a4 {
+    /// A 789A-4 on a `ScriptedPort`, which echoes moves, reports the motor moving for the first `busy_polls` status
+    /// polls after each, and is off every switch. Returned with the commands sent to it.
+    pub fn scripted(busy_polls: usize) -> (Mp789a4, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        let mut remaining = 0;
+        let (comms, sent) = Serial::scripted(move |command| match command.trim_end() {
+            "^" if remaining > 0 => {
+                remaining -= 1;
+                "1\r\n".to_owned()
+            }
+            "^" | "]" => "0\r\n".to_owned(),
+            c if c.starts_with(['+', '-']) => {
+                remaining = busy_polls;
+                format!("{}\r\n", c)
+            }
+            _ => "\r\n".to_owned(),
+        });
+
+        let mp = Mp789a4 {
+            comms,
+            identification: String::new(),
+            position: 0,
+            moving: false,
+            homing: false,
+            jog: None,
+            origin: None,
+            settings: Settings::default(),
+        };
+        (mp, sent)
+    }
+}
+
 // Public interface.
 impl MotionControlDriver for Mp789a4 {
     fn home(&mut self) -> Result<(), serialport::Error> {
@@ -269,6 +516,23 @@ impl MotionControlDriver for Mp789a4 {
         self.position
     }
 
+    fn read_position(&mut self) -> Result<Option<i64>, serialport::Error> {
+        let Some(origin) = self.origin else {
+            return Ok(None);
+        };
+
+        Ok(self.read_counter()?.map(|count| count - origin))
+    }
+
+    fn limit_status(&mut self) -> Result<LimitStatus, serialport::Error> {
+        self.poll_limits()?.ok_or_else(|| {
+            serialport::Error::new(
+                serialport::ErrorKind::InvalidInput,
+                format!("Unexpected limit status from {}: {:?}", SHORT_NAME, self.comms.recv_string()),
+            )
+        })
+    }
+
     fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Stopping {}.", self.short_name());
         self.comms.xfer(b"@\r")?;
@@ -281,24 +545,12 @@ impl MotionControlDriver for Mp789a4 {
     fn is_moving(&mut self) -> Result<bool, serialport::Error> {
         // If we cannot acquire the hardware lock, then assume we are moving (could also be stopping, but we arent stopped yet).
 
-        match self.moving {
-            true => return Ok(true),
-            false => {}
+        if self.moving {
+            return Ok(true);
         }
 
         // Finally, ask the device if its moving.
-        self.comms.xfer(b"^\r")?;
-        if self.comms.recv_contains(b"0")
-            && !self.comms.recv_contains(b"+")
-            && !self.comms.recv_contains(b"-")
-        {
-            self.moving = false;
-            Ok(false)
-        } else {
-            // If we cannot determine if the device is moving, assume it is.
-            // self.moving = true;
-            Ok(true)
-        }
+        self.poll_moving()
     }
 
     fn is_homing(&mut self) -> bool {
@@ -326,6 +578,64 @@ impl MotionControlDriver for Mp789a4 {
         }
     }
 
+    fn jog(&mut self, velocity: i64) -> Result<(), serialport::Error> {
+        if velocity != 0 {
+            log::debug!("Jogging at {} steps/s.", velocity);
+            self.comms.xfer(format!("M{:+}\r", velocity).as_bytes())?;
+            self.jog = Some((Instant::now(), velocity));
+            return Ok(());
+        }
+
+        // Soft stop, as when homing.
+        self.comms.xfer(b"@\r")?;
+        while self.poll_moving()? {
+            log::debug!("Waiting for the jog to stop.");
+            sleep(Duration::from_millis(100));
+        }
+
+        // Without a position counter the distance can only be estimated from the time taken, ignoring the ramps at
+        // either end.
+        if let Some((start, velocity)) = self.jog.take() {
+            self.position = match self.read_position()? {
+                Some(position) => position,
+                None => self.position + (velocity as f64 * start.elapsed().as_secs_f64()).round() as i64,
+            };
+        }
+
+        Ok(())
+    }
+
+    fn settings(&mut self) -> Vec<DriverSetting> {
+        self.settings.describe()
+    }
+
+    fn capabilities(&mut self) -> MotionCapabilities {
+        MotionCapabilities {
+            homing: true,
+            encoder: self.origin.is_some(),
+            limit_switches: true,
+            constant_velocity: true,
+        }
+    }
+
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error> {
+        self.settings.set(name, &value)?;
+
+        // The others are only used when homing.
+        match name {
+            VELOCITY if self.settings.velocity > 0 => {
+                self.comms.xfer(format!("V{}\r", self.settings.velocity).as_bytes())?; // Slew velocity.
+            }
+            ACCELERATION if self.settings.acceleration > 0 => {
+                self.comms.xfer(format!("K{}\r", self.settings.acceleration).as_bytes())?; // Ramp slope.
+            }
+            _ => {}
+        }
+
+        log::info!("{} of {} set to {}.", name, SHORT_NAME, value);
+        Ok(())
+    }
+
     fn short_name(&mut self) -> String {
         SHORT_NAME.to_string()
     }
@@ -333,6 +643,10 @@ impl MotionControlDriver for Mp789a4 {
     fn long_name(&mut self) -> String {
         LONG_NAME.to_string()
     }
+
+    fn identification(&mut self) -> String {
+        self.identification.clone()
+    }
 }
 
 //
@@ -342,21 +656,17 @@ impl MotionControlDriver for Mp789a4 {
 
 pub struct Mp789a4Virtual {
     position: i64,
-    long_name: String,
-    short_name: String,
+    jog: Option<(Instant, i64)>,
+    settings: Settings,
 }
 
 impl Mp789a4Virtual {
-    pub fn new(port_name: String) -> Result<Mp789a4Virtual, serialport::Error> {
-        let mut dev = Mp789a4Virtual {
+    pub fn new(_port_name: String) -> Result<Mp789a4Virtual, serialport::Error> {
+        Ok(Mp789a4Virtual {
             position: 0,
-            long_name: "McPherson 789A-4".to_string(),
-            short_name: "MP789A4".to_string(),
-        };
-
-        dev.home()?;
-
-        Ok(dev)
+            jog: None,
+            settings: Settings::default(),
+        })
     }
 
     fn move_relative(&mut self, steps: i64) -> Result<(), serialport::Error> {
@@ -375,6 +685,17 @@ impl MotionControlDriver for Mp789a4Virtual {
         self.position
     }
 
+    fn read_position(&mut self) -> Result<Option<i64>, serialport::Error> {
+        Ok(Some(self.position))
+    }
+
+    fn limit_status(&mut self) -> Result<LimitStatus, serialport::Error> {
+        Ok(LimitStatus {
+            moving: self.jog.is_some(),
+            ..LimitStatus::default()
+        })
+    }
+
     fn stop(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         log::info!("Stopping {}.", self.short_name());
 
@@ -392,12 +713,41 @@ impl MotionControlDriver for Mp789a4Virtual {
     fn move_to(
         &mut self,
         position: i64,
-        backlash_correction: i64,
+        _backlash_correction: i64,
     ) -> Result<(), serialport::Error> {
-        self.position = position;
+        self.move_relative(position - self.position)
+    }
+
+
+    fn jog(&mut self, velocity: i64) -> Result<(), serialport::Error> {
+        if let Some((start, velocity)) = self.jog.take() {
+            self.position += (velocity as f64 * start.elapsed().as_secs_f64()).round() as i64;
+        }
+
+        if velocity != 0 {
+            self.jog = Some((Instant::now(), velocity));
+        }
+
         Ok(())
     }
 
+    fn settings(&mut self) -> Vec<DriverSetting> {
+        self.settings.describe()
+    }
+
+    fn capabilities(&mut self) -> MotionCapabilities {
+        MotionCapabilities {
+            homing: true,
+            encoder: true,
+            limit_switches: false,
+            constant_velocity: true,
+        }
+    }
+
+    fn set_setting(&mut self, name: &str, value: SettingValue) -> Result<(), serialport::Error> {
+        self.settings.set(name, &value)
+    }
+
     fn short_name(&mut self) -> String {
         SHORT_NAME.to_string()
     }
@@ -405,4 +755,27 @@ impl MotionControlDriver for Mp789a4Virtual {
     fn long_name(&mut self) -> String {
         LONG_NAME.to_string()
     }
+
+    fn identification(&mut self) -> String {
+        format!("Virtual {}", SHORT_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_to_ends_once_the_controller_reports_not_moving() {
+        let (mut mp, sent) = Mp789a4::scripted(1);
+
+        mp.move_to(100, 0).unwrap();
+        mp.move_to(40, 20).unwrap(); // Down, so past and back up by the backlash correction.
+
+        assert_eq!(mp.get_position(), 40);
+        assert!(!mp.is_moving().unwrap());
+
+        let moves: Vec<String> = sent.lock().unwrap().iter().filter(|c| c.starts_with(['+', '-'])).cloned().collect();
+        assert_eq!(moves, vec!["+100\r", "-80\r", "+20\r"]);
+    }
 }
@@ -0,0 +1,72 @@
+use super::ki_6485::{Ki6485, Ki6485Virtual};
+use super::mp_789a_4::{Mp789a4, Mp789a4Virtual};
+use super::{DetectorDriver, MotionControlDriver};
+
+// Every supported model, by the name the user selects it by, and how to connect to it.
+// Each real model is followed by its virtual variant, which simulates the device without any hardware attached.
+// Real models can be probed for, to find which port they are on. Probes are tried in the order listed, so those least
+// likely to upset a different device come first.
+
+const KI_6485_SAMPLES: i32 = 10; // Readings averaged by the picoammeter per detection.
+
+pub struct MotionControlModel {
+    pub name: &'static str,
+    pub connect: fn(String) -> Result<Box<dyn MotionControlDriver>, serialport::Error>,
+    pub probe: Option<fn(String) -> bool>,
+}
+
+pub struct DetectorModel {
+    pub name: &'static str,
+    pub connect: fn(String) -> Result<Box<dyn DetectorDriver>, serialport::Error>,
+    pub probe: Option<fn(String) -> bool>,
+}
+
+pub const MOTION_CONTROL_MODELS: &[MotionControlModel] = &[
+    MotionControlModel {
+        name: "MP 789A-4",
+        connect: connect_mp_789a_4,
+        probe: Some(Mp789a4::probe),
+    },
+    MotionControlModel {
+        name: "MP 789A-4 (Virtual)",
+        connect: connect_mp_789a_4_virtual,
+        probe: None,
+    },
+];
+
+pub const DETECTOR_MODELS: &[DetectorModel] = &[
+    DetectorModel {
+        name: "KI 6485",
+        connect: connect_ki_6485,
+        probe: Some(Ki6485::probe),
+    },
+    DetectorModel {
+        name: "KI 6485 (Virtual)",
+        connect: connect_ki_6485_virtual,
+        probe: None,
+    },
+];
+
+pub fn motion_control_model(name: &str) -> Option<&'static MotionControlModel> {
+    MOTION_CONTROL_MODELS.iter().find(|m| m.name == name)
+}
+
+pub fn detector_model(name: &str) -> Option<&'static DetectorModel> {
+    DETECTOR_MODELS.iter().find(|m| m.name == name)
+}
+
+fn connect_mp_789a_4(port_name: String) -> Result<Box<dyn MotionControlDriver>, serialport::Error> {
+    Ok(Box::new(Mp789a4::new(port_name)?))
+}
+
+fn connect_mp_789a_4_virtual(port_name: String) -> Result<Box<dyn MotionControlDriver>, serialport::Error> {
+    Ok(Box::new(Mp789a4Virtual::new(port_name)?))
+}
+
+fn connect_ki_6485(port_name: String) -> Result<Box<dyn DetectorDriver>, serialport::Error> {
+    Ok(Box::new(Ki6485::new(port_name, KI_6485_SAMPLES)?))
+}
+
+fn connect_ki_6485_virtual(port_name: String) -> Result<Box<dyn DetectorDriver>, serialport::Error> {
+    Ok(Box::new(Ki6485Virtual::new(port_name, KI_6485_SAMPLES)))
+}
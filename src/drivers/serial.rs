@@ -1,20 +1,24 @@
 // use log::*;
 use serialport::SerialPort;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 
 const TIMEOUT: u64 = 50;
+const RECV_LEN: usize = 64; // Long enough for the longest response, the 6485's identification.
 
 pub struct Serial {
     port: Arc<Mutex<Box<dyn SerialPort>>>,
-    recv: [u8; 32],
+    log_target: String, // Ends in the port name, so that the traffic of each device has its own source in the log.
+    replies: HashMap<Vec<u8>, String>, // The last reply to each command sent with `xfer`.
+    recv: [u8; RECV_LEN],
     write_delay: u64,
 }
 
 impl Serial {
     pub fn new(port_name: String, write_delay: u64) -> Result<Serial, serialport::Error> {
-        let port = serialport::new(port_name, 9600)
+        let port = serialport::new(port_name.clone(), 9600)
             .timeout(Duration::from_millis(TIMEOUT))
             .open()?;
 
@@ -22,7 +26,9 @@ impl Serial {
 
         Ok(Serial {
             port: Arc::new(Mutex::new(port)),
-            recv: [0; 32],
+            log_target: format!("{}::{}", module_path!(), port_name),
+            replies: HashMap::new(),
+            recv: [0; RECV_LEN],
             write_delay,
         })
     }
@@ -31,32 +37,253 @@ impl Serial {
         self.recv.windows(seq.len()).any(|window| window == seq)
     }
 
-    pub fn get_recv(&self) -> [u8; 32] {
+    pub fn get_recv(&self) -> [u8; RECV_LEN] {
         self.recv
     }
 
+    /// Returns the receive buffer as text, without trailing padding or line endings.
+    pub fn recv_string(&self) -> String {
+        String::from_utf8_lossy(&self.recv)
+            .trim_end_matches('\0')
+            .trim()
+            .to_string()
+    }
+
     pub fn _write(&mut self, buf: &[u8]) -> Result<(), serialport::Error> {
-        log::info!("Writing: {:?}", buf);
+        log::info!(target: &self.log_target, "<- {}", String::from_utf8_lossy(buf).escape_debug());
+        self.send(buf)
+    }
+
+    fn send(&mut self, buf: &[u8]) -> Result<(), serialport::Error> {
         let retval = Ok(self.port.lock().unwrap().write_all(buf)?);
         sleep(Duration::from_millis(self.write_delay));
         retval
     }
 
     pub fn xfer_sleep(&mut self, buf: &[u8], sleep_time: u64) -> Result<(), serialport::Error> {
-        log::info!("Writing: {:?}", buf);
+        log::info!(target: &self.log_target, "<- {}", String::from_utf8_lossy(buf).escape_debug());
         let retval = Ok(self.port.lock().unwrap().write_all(buf)?);
         sleep(Duration::from_millis(sleep_time));
         retval
     }
 
+    /// Reads a response into the receive buffer, replacing the previous one.
+    ///
+    /// Fails if nothing arrives within the timeout. Otherwise reads on until the device goes quiet, since responses may
+    /// arrive in several pieces.
     pub fn _read(&mut self) -> Result<usize, serialport::Error> {
-        let retval = self.port.lock().unwrap().read(&mut self.recv)?;
-        log::info!("Read: {:?}", self.recv);
+        let retval = self.receive(Duration::from_millis(TIMEOUT))?;
+
+        log::info!(target: &self.log_target, "-> {}", String::from_utf8_lossy(&self.recv[..retval]).escape_debug());
+        Ok(retval)
+    }
+
+    /// Waits up to `wait` for a response to begin, then reads on until the device goes quiet.
+    fn receive(&mut self, wait: Duration) -> Result<usize, serialport::Error> {
+        self.recv = [0; RECV_LEN];
+
+        let mut port = self.port.lock().unwrap();
+        port.set_timeout(wait)?;
+        let first = port.read(&mut self.recv);
+        port.set_timeout(Duration::from_millis(TIMEOUT))?;
+
+        let mut retval = first?;
+        while retval < RECV_LEN {
+            match port.read(&mut self.recv[retval..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => retval += n,
+            }
+        }
+        drop(port);
+
         Ok(retval)
     }
 
+    /// Sends a command and reads its response.
+    ///
+    /// A command answered the same as last time, such as a status poll while nothing changes, is only logged at trace
+    /// level, so that polling does not bury everything else in the log.
     pub fn xfer(&mut self, buf: &[u8]) -> Result<usize, serialport::Error> {
-        self._write(buf)?;
-        self._read()
+        self.xfer_wait(buf, Duration::from_millis(TIMEOUT))
+    }
+
+    /// Like `xfer`, but waits up to `wait` for the response to begin, for commands which take long to answer.
+    pub fn xfer_wait(&mut self, buf: &[u8], wait: Duration) -> Result<usize, serialport::Error> {
+        self.send(buf)?;
+        let read = self.receive(wait);
+
+        let reply = read.as_ref().ok().map(|&n| String::from_utf8_lossy(&self.recv[..n]).escape_debug().to_string());
+        let level = match reply.is_some() && self.replies.get(buf) == reply.as_ref() {
+            true => log::Level::Trace,
+            false => log::Level::Info,
+        };
+
+        log::log!(target: &self.log_target, level, "<- {}", String::from_utf8_lossy(buf).escape_debug());
+        if let Some(reply) = reply {
+            log::log!(target: &self.log_target, level, "-> {}", reply);
+            self.replies.insert(buf.to_vec(), reply);
+        }
+
+        read
+    }
+}
+/// A port which answers each command by a script rather than a device, so drivers can be tested without hardware.
+#[cfg(test)]
+pub struct ScriptedPort {
+    script: Box<dyn FnMut(&str) -> String + Send>, // The answer to each command written.
+    sent: Arc<Mutex<Vec<String>>>,                // Every command written, in order.
+    pending: Vec<u8>,
+}
+
+#[cfg(test)]
+impl Serial {
+    /// Opens a `ScriptedPort` answering by `script`, returning it with the list of commands sent to it.
+    pub fn scripted(script: impl FnMut(&str) -> String + Send + 'static) -> (Serial, Arc<Mutex<Vec<String>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let port = ScriptedPort {
+            script: Box::new(script),
+            sent: sent.clone(),
+            pending: Vec::new(),
+        };
+
+        let serial = Serial {
+            port: Arc::new(Mutex::new(Box::new(port))),
+            log_target: format!("{}::scripted", module_path!()),
+            replies: HashMap::new(),
+            recv: [0; RECV_LEN],
+            write_delay: 0,
+        };
+        (serial, sent)
+    }
+}
+
+#[cfg(test)]
+impl std::io::Read for ScriptedPort {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pending.is_empty() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for ScriptedPort {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let command = String::from_utf8_lossy(buf).to_string();
+        self.pending.extend((self.script)(&command).into_bytes());
+        self.sent.lock().unwrap().push(command);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl SerialPort for ScriptedPort {
+    fn name(&self) -> Option<String> {
+        Some("scripted".to_owned())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(9600)
+    }
+
+    fn data_bits(&self) -> serialport::Result<serialport::DataBits> {
+        Ok(serialport::DataBits::Eight)
+    }
+
+    fn flow_control(&self) -> serialport::Result<serialport::FlowControl> {
+        Ok(serialport::FlowControl::None)
+    }
+
+    fn parity(&self) -> serialport::Result<serialport::Parity> {
+        Ok(serialport::Parity::None)
     }
-}
\ No newline at end of file
+
+    fn stop_bits(&self) -> serialport::Result<serialport::StopBits> {
+        Ok(serialport::StopBits::One)
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(TIMEOUT)
+    }
+
+    fn set_baud_rate(&mut self, _: u32) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_data_bits(&mut self, _: serialport::DataBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_flow_control(&mut self, _: serialport::FlowControl) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_parity(&mut self, _: serialport::Parity) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_stop_bits(&mut self, _: serialport::StopBits) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn set_timeout(&mut self, _: Duration) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, _: bool) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(true)
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.pending.len() as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _: serialport::ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Err(serialport::Error::new(serialport::ErrorKind::Unknown, "A scripted port cannot be cloned."))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::middleware::unix_time;
+
+// In-app record of everything logged through `log`, as shown in the Data Log tab.
+// Records are also passed on to the platform logger, so stderr (or the browser console) keeps working as before.
+
+const MAX_ENTRIES: usize = 100_000; // The oldest entries are dropped beyond this.
+
+static LOG: Mutex<Log> = Mutex::new(Log {
+    entries: VecDeque::new(),
+    dropped: 0,
+});
+
+struct Log {
+    entries: VecDeque<Entry>,
+    dropped: usize, // The number of the oldest entry kept. Entries are numbered from 0 in the order logged.
+}
+
+#[derive(Clone)]
+pub struct Entry {
+    pub timestamp: f64, // Seconds since the Unix epoch.
+    pub level: log::Level,
+    pub source: String, // Module the record came from, e.g. "ki_6485" or "scan", or the port of serial traffic.
+    pub message: String,
+}
+
+impl Entry {
+    /// Formats the entry as one line of a log file.
+    pub fn line(&self) -> String {
+        format!("{} {:5} [{}] {}", time_of_day(self.timestamp), self.level, self.source, self.message)
+    }
+}
+
+struct Logger {
+    inner: Box<dyn log::Log>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        captured(metadata) || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if captured(record.metadata()) {
+            let entry = Entry {
+                timestamp: unix_time(),
+                level: record.level(),
+                source: record.target().rsplit("::").next().unwrap_or_default().to_owned(),
+                message: record.args().to_string(),
+            };
+
+            let mut log = LOG.lock().unwrap();
+            if log.entries.len() >= MAX_ENTRIES {
+                log.entries.pop_front();
+                log.dropped += 1;
+            }
+            log.entries.push_back(entry);
+        }
+
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Everything of ours down to debug level is kept, but only info and above from the libraries underneath.
+fn captured(metadata: &log::Metadata) -> bool {
+    match metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+        true => metadata.level() <= log::Level::Debug,
+        false => metadata.level() <= log::Level::Info,
+    }
+}
+
+/// Installs the in-app log, forwarding every record to `inner`, which logs up to `inner_level`, as well.
+pub fn init(inner: Box<dyn log::Log>, inner_level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+    log::set_boxed_logger(Box::new(Logger { inner }))?;
+    log::set_max_level(inner_level.max(log::LevelFilter::Debug));
+    Ok(())
+}
+
+/// Runs `f` on the entries logged so far, oldest first, and the number of the first of them.
+///
+/// The log is locked meanwhile, so `f` must not log.
+pub fn with_entries<R>(f: impl FnOnce(&VecDeque<Entry>, usize) -> R) -> R {
+    let log = LOG.lock().unwrap();
+    f(&log.entries, log.dropped)
+}
+
+/// The entries which pass the filters of the Data Log, by number.
+///
+/// Only entries logged since the last update are filtered, unless the filters changed, as filtering every entry on
+/// every frame gets slow over a long session.
+#[derive(Default)]
+pub struct Filtered {
+    pub matches: Vec<usize>,  // Oldest first.
+    pub sources: Vec<String>, // Of every entry seen, sorted.
+    applied: Option<(log::LevelFilter, Option<String>, String)>, // The filters `matches` are for.
+    seen: usize, // The number of the next entry to filter.
+}
+
+impl Filtered {
+    /// Brings the matches up to date with the entries logged and the filters given.
+    pub fn update(&mut self, level: log::LevelFilter, source: &Option<String>, search: &str) {
+        let filters = (level, source.clone(), search.to_lowercase());
+        if self.applied.as_ref() != Some(&filters) {
+            self.matches.clear();
+            self.seen = 0;
+        }
+        let (level, source, search) = self.applied.insert(filters);
+
+        with_entries(|entries, dropped| {
+            let gone = self.matches.partition_point(|&n| n < dropped);
+            self.matches.drain(..gone);
+
+            for (n, entry) in entries.iter().enumerate().map(|(i, e)| (dropped + i, e)).skip(self.seen.saturating_sub(dropped)) {
+                if let Err(idx) = self.sources.binary_search(&entry.source) {
+                    self.sources.insert(idx, entry.source.clone());
+                }
+
+                let shown = entry.level <= *level
+                    && source.as_ref().map_or(true, |s| *s == entry.source)
+                    && (search.is_empty() || entry.message.to_lowercase().contains(search.as_str()));
+                if shown {
+                    self.matches.push(n);
+                }
+            }
+
+            self.seen = dropped + entries.len();
+        });
+    }
+}
+
+/// Writes every entry logged so far to `path`, regardless of what is shown.
+pub fn save(path: &str) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    with_entries(|entries, _| -> std::io::Result<()> {
+        for entry in entries {
+            writeln!(file, "{}", entry.line())?;
+        }
+        Ok(())
+    })?;
+
+    file.flush()
+}
+
+/// Returns the UTC time of day as "HH:MM:SS.mmm".
+pub fn time_of_day(timestamp: f64) -> String {
+    let ms = (timestamp * 1000.0) as u64 % 86_400_000;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
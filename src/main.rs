@@ -2,6 +2,8 @@
 #![allow(rustdoc::missing_crate_level_docs)] // it's an example
 
 use std::any::Any;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
 use std::vec;
 
 use eframe::egui;
@@ -11,9 +13,27 @@ use egui::{Frame, Widget};
 use serialport::SerialPortInfo;
 use egui_dock::{DockArea, DockState, NodeIndex};
 
+pub mod analysis;
+pub mod connect;
+pub mod datafile;
 pub mod drivers;
+pub mod event_log;
 pub mod middleware;
-use middleware::{MotionController, MovementAxesIndices, Detector};
+pub mod motion;
+pub mod profile;
+pub mod scan;
+pub mod units;
+use analysis::{DerivedQuantity, Spectrum};
+use connect::{ConnectEvent, DetectEvent, DeviceRequest, FoundDevice};
+use datafile::journal::{self, Journal};
+use datafile::{DataSet, Metadata};
+use middleware::{Approach, Axis, BacklashMode, HomeBehavior, MotionState, MotionController, MovementAxesIndices, Detector, DetectorRoleIndices, Scan, ScanKind};
+use drivers::{DriverSetting, SettingKind, SettingValue};
+use middleware::MotionControlMiddleware;
+use motion::MotionHandle;
+use profile::{DetectorRole, DetectorSetup, MachineProfile, MotionControlSetup};
+use scan::{ScanDefinition, ScanEvent, ScanHandle};
+use units::AxisUnit;
 
 // use rand::prelude::*;
 
@@ -22,7 +42,10 @@ use crate::middleware::DetectorMiddleware;
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    // Log to the Data Log tab, and to stderr (if you run with `RUST_LOG=debug`).
+    let logger = env_logger::Builder::from_default_env().build();
+    let level = logger.filter();
+    event_log::init(Box::new(logger), level).expect("The logger is only set once.");
 
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -43,7 +66,7 @@ fn main() -> eframe::Result<()> {
             // This gives us image support:
             egui_extras::install_image_loaders(&cc.egui_ctx);
 
-            Box::<Mcs>::default()
+            Box::new(Mcs::new(cc))
         }),
     )
 }
@@ -51,8 +74,8 @@ fn main() -> eframe::Result<()> {
 // When compiling to web using trunk:
 #[cfg(target_arch = "wasm32")]
 fn main() {
-    // Redirect `log` message to `console.log` and friends:
-    eframe::WebLogger::init(log::LevelFilter::Debug).ok();
+    // Redirect `log` message to the Data Log tab, and to `console.log` and friends:
+    event_log::init(Box::new(eframe::WebLogger::new(log::LevelFilter::Debug)), log::LevelFilter::Debug).ok();
 
     let web_options = eframe::WebOptions::default();
 
@@ -65,7 +88,7 @@ fn main() {
                     // This gives us image support:
                     egui_extras::install_image_loaders(&cc.egui_ctx);
 
-                    Box::<Mcs>::default()
+                    Box::new(Mcs::new(cc))
                 }),
             )
             .await
@@ -108,14 +131,19 @@ struct McsTabs {
     num_det_devs: usize,
     sel_mc_port: Vec<String>,
     sel_det_port: Vec<String>,
+
+    sel_mc_serial: Vec<Option<String>>, // USB serial number of the device on the selected port, if known.
+    sel_det_serial: Vec<Option<String>>,
     sel_mc_model: Vec<String>,
     sel_det_model: Vec<String>,
     sel_mc_nick: Vec<String>,
     sel_det_nick: Vec<String>,
 
     // Controls
-    pos_target: f32,
-    pos_curr: f32,
+    x_unit: AxisUnit,
+    pos_target: f32, // nm
+    pos_curr: f32, // nm
+    // Scans step evenly in the unit they are defined in, `x_unit`.
     scan_start: f32,
     scan_end: f32,
     scan_step: f32,
@@ -132,9 +160,56 @@ struct McsTabs {
     samp_scan_step: f32,
     samp_scan_repeats: u32,
 
-    detector_data: Vec<Vec<f64>>, // Outer vec is per-detector, inner vec is per-scan data.
+    // Plot visibility. Scans are recorded by all detectors at once, so scan visibility applies to every detector.
+    det_visible: Vec<bool>,
+    scan_visible: Vec<bool>,
+
+    plot_follow: bool, // Keep the plot bounds on the incoming data during a scan.
+
+    // Data files
+    operator: String,
+    data_path: String,
+    imported: Vec<(DataSet, bool)>, // Previously saved scans and whether they are shown.
+    journal: Option<Journal>, // Every point as it is acquired, so a crash loses nothing.
+    journaled_detectors: usize, // How many of the connected detectors the journal was told of. 0 before it is started.
+
+    scan: Option<ScanHandle>,
+    scan_pos: Option<f64>, // nm, where the running scan is measuring next.
+    motions: Vec<MotionHandle>, // Manual movements in progress, at most one per axis.
+    jog_axis: Option<Axis>, // Jogged by the arrow keys.
 
+    connd_mtn_ctrlrs: Vec<MotionController>,
     connd_detectors: Vec<Detector>,
+    mai: MovementAxesIndices,
+    dri: DetectorRoleIndices,
+
+    // Reflectance / transmittance
+    derived_quantity: DerivedQuantity,
+    derived_step: f64,
+    derived_path: String,
+    derived: Option<Spectrum>,
+
+    // Machine profile
+    profile: MachineProfile, // As last loaded. Holds the setup of devices which are not connected right now.
+    profile_path: String,
+
+    // Data Log filters
+    log_level: log::LevelFilter,
+    log_source: Option<String>, // None shows every source.
+    log_search: String,
+    log_path: String,
+    log_filtered: event_log::Filtered,
+
+    // Dialogs requested from within the tabs, shown by `Mcs` on the next frame.
+    pending_dialog: Option<(DialogType, String)>,
+}
+
+// Selected devices bound to a USB serial number which is not plugged in, by their index in the Device Manager.
+#[derive(Default)]
+struct MissingDevices {
+    motion_controllers: Vec<usize>,
+    detectors: Vec<usize>,
+    descriptions: Vec<String>,
 }
 
 impl egui_dock::TabViewer for McsTabs {
@@ -158,37 +233,76 @@ impl egui_dock::TabViewer for McsTabs {
 
 impl McsTabs {
     fn device_controls(&mut self, ui: &mut egui::Ui) {
+        self.update_positions();
+        self.jog_keys(ui);
+
+        // The statuses are updated in the background.
+        if !self.connd_mtn_ctrlrs.is_empty() {
+            ui.ctx().request_repaint_after(middleware::MONITOR_INTERVAL);
+        }
+
         ui.vertical(|ui| {
+            egui::CollapsingHeader::new("Status").default_open(true).show(ui, |ui| {
+                self.device_status(ui);
+            });
             egui::CollapsingHeader::new("Main Drive").show(ui, |ui| {
+                let unit = self.x_unit.as_str().to_owned();
+
+                ui.horizontal(|ui| {
+                    ui.label("Units");
+                    self.x_unit_combo(ui, "Main Drive Units");
+                });
+
                 ui.label("Manual Control");
                 ui.horizontal(|ui| {
-                    ui.button("Home");
-                    ui.label("Position [nm]");
-                    ui.add(egui::DragValue::new(&mut self.pos_target).speed(0.1));
-                    ui.button("Move");
-                    ui.label(format!("{} nm", self.pos_curr));
+                    self.home_button(ui, Axis::MainDrive);
+                    ui.label(format!("Position [{}]", unit));
+                    let mut target = self.x_unit.nm_to_unit(self.pos_target as f64);
+                    if ui.add(egui::DragValue::new(&mut target).speed(0.1)).changed() {
+                        self.pos_target = self.x_unit.unit_to_nm(target) as f32;
+                    }
+                    self.move_button(ui, Axis::MainDrive, self.pos_target as f64);
+                    ui.label(format!("{:.3} {}", self.x_unit.nm_to_unit(self.pos_curr as f64), unit));
                 });
+                self.jog_controls(ui, Axis::MainDrive);
 
                 ui.separator();
 
                 ui.label("Scanning Control");
                 ui.horizontal(|ui| {
-                    ui.label("Start [nm]");
+                    ui.label(format!("Start [{}]", unit));
                     ui.add(egui::DragValue::new(&mut self.scan_start).speed(0.1));
-                    ui.label("End [nm]");
+                    ui.label(format!("End [{}]", unit));
                     ui.add(egui::DragValue::new(&mut self.scan_end).speed(0.1));
-                    ui.label("Step [nm]");
+                    ui.label(format!("Step [{}]", unit));
                     ui.add(egui::DragValue::new(&mut self.scan_step).speed(0.1));
                     ui.label("Repeats");
                     ui.add(egui::DragValue::new(&mut self.scan_repeats).speed(0.1));
                 });
                 ui.horizontal(|ui| {
-                    ui.button("Start");
-                    ui.button("Pause");
-                    ui.button("Stop");
-                });
+                    let can_start = !self.axis_busy(Axis::MainDrive) && !self.connd_detectors.is_empty();
+                    if ui.add_enabled(can_start, egui::Button::new("Start")).clicked() {
+                        self.start_scan(ui.ctx());
+                    }
 
-                ui.button("Scan");
+                    let paused = self.scan.as_ref().is_some_and(|s| s.is_paused());
+                    let pause_text = if paused { "Resume" } else { "Pause" };
+                    if ui.add_enabled(self.scan.is_some(), egui::Button::new(pause_text)).clicked() {
+                        if let Some(scan) = &self.scan {
+                            scan.set_paused(!paused);
+                        }
+                    }
+
+                    if ui.add_enabled(self.scan.is_some(), egui::Button::new("Stop")).clicked() {
+                        if let Some(scan) = &self.scan {
+                            scan.stop();
+                        }
+                    }
+
+                    if self.scan.is_some() {
+                        ui.spinner();
+                    }
+                });
 
             });
             egui::CollapsingHeader::new("Filter Wheel").show(ui, |ui| {
@@ -197,26 +311,32 @@ impl McsTabs {
             egui::CollapsingHeader::new("Sample").show(ui, |ui| {
                 ui.label("Manual Control");
                 ui.horizontal(|ui| {
-                    ui.button("Home");
+                    self.home_button(ui, Axis::SampleRotation);
                     ui.label("Rotation [deg]");
                     ui.add(egui::DragValue::new(&mut self.samp_rot_target).speed(0.1));
-                    ui.button("Move");
+                    self.move_button(ui, Axis::SampleRotation, self.samp_rot_target as f64 + self.user_zero(Axis::SampleRotation));
                     ui.label(format!("{} deg", self.samp_rot_curr));
+                    self.zero_buttons(ui, Axis::SampleRotation);
                 });
+                self.jog_controls(ui, Axis::SampleRotation);
                 ui.horizontal(|ui| {
-                    ui.button("Home");
+                    self.home_button(ui, Axis::SampleAngle);
                     ui.label("Angle [deg]");
                     ui.add(egui::DragValue::new(&mut self.samp_ang_target).speed(0.1));
-                    ui.button("Move");
+                    self.move_button(ui, Axis::SampleAngle, self.samp_ang_target as f64 + self.user_zero(Axis::SampleAngle));
                     ui.label(format!("{} deg", self.samp_ang_curr));
+                    self.zero_buttons(ui, Axis::SampleAngle);
                 });
+                self.jog_controls(ui, Axis::SampleAngle);
                 ui.horizontal(|ui| {
-                    ui.button("Home");
-                    ui.label("Translation [nm]");
+                    self.home_button(ui, Axis::SampleTranslation);
+                    ui.label("Translation [mm]");
                     ui.add(egui::DragValue::new(&mut self.samp_tran_target).speed(0.1));
-                    ui.button("Move");
-                    ui.label(format!("{} nm", self.samp_tran_curr));
+                    self.move_button(ui, Axis::SampleTranslation, self.samp_tran_target as f64 + self.user_zero(Axis::SampleTranslation));
+                    ui.label(format!("{} mm", self.samp_tran_curr));
+                    self.zero_buttons(ui, Axis::SampleTranslation);
                 });
+                self.jog_controls(ui, Axis::SampleTranslation);
 
                 ui.label("Scanning Control");
                 ui.horizontal(|ui| {
@@ -243,71 +363,1511 @@ impl McsTabs {
                 });
                 
                 ui.horizontal(|ui| {
-                    ui.button("Start");
-                    ui.button("Pause");
-                    ui.button("Stop");
+                    let _ = ui.button("Start");
+                    let _ = ui.button("Pause");
+                    let _ = ui.button("Stop");
                 });
             });
             egui::CollapsingHeader::new("Detector").show(ui, |ui| {
-                ui.label("Body");
+                ui.label("Normalization");
+                ui.horizontal(|ui| {
+                    ui.label("Sample");
+                    self.detector_role_combo(ui, "Sample Detector", true);
+                    ui.label("Reference");
+                    self.detector_role_combo(ui, "Reference Detector", false);
+                });
+
+                if self.dri.sample_idx.is_some() && self.dri.sample_idx == self.dri.ref_idx {
+                    ui.label("The sample and reference must be different detectors.");
+                }
+
+                for i in 0..self.connd_detectors.len() {
+                    self.detector_settings(ui, i);
+                }
             });
         });
     }
 
-    // TODO: Make a data_record function.
+    /// Shows the settings particular to the model of the detector at `det_idx`. They cannot be changed during a scan.
+    fn detector_settings(&mut self, ui: &mut egui::Ui, det_idx: usize) {
+        let det = &self.connd_detectors[det_idx];
+        if det.settings().is_empty() {
+            return;
+        }
+
+        let mut changed = None;
+        let mut acquire_zero = false;
+
+        egui::CollapsingHeader::new(self.det_name(det_idx))
+            .id_source(format!("Detector Settings {}", det_idx))
+            .show(ui, |ui| {
+                ui.add_enabled_ui(self.scan.is_none(), |ui| {
+                    changed = settings_grid(ui, &format!("detector_settings_{}", det_idx), det.settings());
+
+                    if det.capabilities().zero_correction {
+                        acquire_zero = ui
+                            .button("Acquire Zero")
+                            .on_hover_text("Measures the offset with the input shunted, and corrects readings by it from now on.")
+                            .clicked();
+                    }
+                });
+            });
+
+        // Points taken from now on belong to a scan recorded with the new settings.
+        if changed.is_some() || acquire_zero {
+            self.new_scan();
+        }
+
+        if let Some((name, value)) = changed {
+            if let Err(e) = self.connd_detectors[det_idx].set_setting(name, value) {
+                self.pending_dialog = Some((DialogType::Error, format!("Setting the {} of {} failed: {}", name, self.det_name(det_idx), e)));
+            }
+        }
+
+        if acquire_zero {
+            if let Err(e) = self.connd_detectors[det_idx].acquire_zero() {
+                self.pending_dialog = Some((DialogType::Error, format!("Acquiring the zero of {} failed: {}", self.det_name(det_idx), e)));
+            }
+        }
+    }
+
+    /// Whether `axis` cannot be moved right now, because no controller drives it or it is already moving.
+    fn axis_busy(&self, axis: Axis) -> bool {
+        let Some(idx) = self.mai.get(axis) else {
+            return true;
+        };
+
+        self.motions.iter().any(|m| m.axis == axis)
+            || self.connd_mtn_ctrlrs[idx].status().state != MotionState::Idle
+            || (axis == Axis::MainDrive && self.scan.is_some())
+    }
+
+    fn home_button(&mut self, ui: &mut egui::Ui, axis: Axis) {
+        let can_home = self.mai.get(axis).is_some_and(|idx| self.connd_mtn_ctrlrs[idx].capabilities().homing);
+
+        if ui.add_enabled(can_home && !self.axis_busy(axis), egui::Button::new("Home")).clicked() {
+            if let Some(idx) = self.mai.get(axis) {
+                let handle = motion::home(axis, &self.connd_mtn_ctrlrs[idx], ui.ctx().clone());
+                self.motions.push(handle);
+            }
+        }
+    }
+
+    /// Shows the Move button of `axis`, and a spinner while it moves. `value` is in the physical unit of the axis.
+    fn move_button(&mut self, ui: &mut egui::Ui, axis: Axis, value: f64) {
+        if ui.add_enabled(!self.axis_busy(axis), egui::Button::new("Move")).clicked() {
+            if let Some(idx) = self.mai.get(axis) {
+                let handle = motion::move_to(axis, &self.connd_mtn_ctrlrs[idx], value, ui.ctx().clone());
+                self.motions.push(handle);
+            }
+        }
+
+        if self.mai.get(axis).is_some() && self.axis_busy(axis) {
+            ui.spinner();
+        }
+    }
+
+    /// Shows the jog buttons of `axis`: steps of the fine and coarse distances, and continuous movement while held down
+    /// if the controller can.
+    fn jog_controls(&mut self, ui: &mut egui::Ui, axis: Axis) {
+        let Some(idx) = self.mai.get(axis) else {
+            return;
+        };
+        let mc = &self.connd_mtn_ctrlrs[idx];
+        let (fine, coarse) = (mc.config().jog_fine, mc.config().jog_coarse);
+        let can_jog = mc.capabilities().constant_velocity;
+        let enabled = !self.axis_busy(axis);
+
+        // A jog only starts as the button is pressed, so one which ended early, e.g. on an error, is not started again
+        // until the button is let go of and pressed anew.
+        let pressed = |r: &egui::Response| r.is_pointer_button_down_on() && r.ctx.input(|i| i.pointer.primary_pressed());
+
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut self.jog_axis, Some(axis), "Jog")
+                .on_hover_text("The left and right arrow keys jog the selected axis by the fine distance, or the coarse distance with Shift held.");
+
+            if can_jog {
+                let r = ui.add_enabled(enabled, egui::Button::new("⏴ Hold")).on_hover_text("Moves down while held.");
+                if pressed(&r) {
+                    self.motions.push(motion::jog_held(axis, &self.connd_mtn_ctrlrs[idx], -1.0, ui.ctx().clone()));
+                }
+            }
+
+            for distance in [-coarse, -fine, fine, coarse] {
+                if ui.add_enabled(enabled, egui::Button::new(format!("{:+} {}", distance, axis.unit()))).clicked() {
+                    self.motions.push(motion::jog(axis, &self.connd_mtn_ctrlrs[idx], distance, ui.ctx().clone()));
+                }
+            }
+
+            if can_jog {
+                let r = ui.add_enabled(enabled, egui::Button::new("Hold ⏵")).on_hover_text("Moves up while held.");
+                if pressed(&r) {
+                    self.motions.push(motion::jog_held(axis, &self.connd_mtn_ctrlrs[idx], 1.0, ui.ctx().clone()));
+                }
+            }
+        });
+    }
+
+    /// Jogs the axis selected for the arrow keys, unless a text field has the keyboard.
+    fn jog_keys(&mut self, ui: &mut egui::Ui) {
+        let Some(axis) = self.jog_axis else {
+            return;
+        };
+        let Some(idx) = self.mai.get(axis) else {
+            return;
+        };
+        if ui.ctx().wants_keyboard_input() || self.axis_busy(axis) {
+            return;
+        }
+
+        let config = self.connd_mtn_ctrlrs[idx].config();
+        let distance = ui.input(|i| {
+            let step = if i.modifiers.shift { config.jog_coarse } else { config.jog_fine };
+            if i.key_pressed(egui::Key::ArrowLeft) {
+                Some(-step)
+            } else if i.key_pressed(egui::Key::ArrowRight) {
+                Some(step)
+            } else {
+                None
+            }
+        });
+
+        if let Some(distance) = distance {
+            self.motions.push(motion::jog(axis, &self.connd_mtn_ctrlrs[idx], distance, ui.ctx().clone()));
+        }
+    }
+
+    /// Reports manual movements which have finished since the last frame.
+    ///
+    /// Called every frame, like `poll_scan`. Continuous jogs end as soon as the mouse button is let go of, wherever the
+    /// pointer is by then.
+    fn poll_motions(&mut self, ctx: &egui::Context) {
+        if !ctx.input(|i| i.pointer.primary_down()) {
+            self.motions.iter().for_each(|m| m.release());
+        }
+
+        let mut errors = Vec::new();
+
+        self.motions.retain(|m| match m.result.try_recv() {
+            Ok(Ok(())) => false,
+            Ok(Err(e)) => {
+                errors.push(e);
+                false
+            }
+            Err(TryRecvError::Empty) => true,
+            // As in `poll_scan`.
+            Err(TryRecvError::Disconnected) => {
+                errors.push(format!("Moving the {} stopped unexpectedly.", m.axis.as_str()));
+                false
+            }
+        });
+
+        if !errors.is_empty() {
+            self.pending_dialog = Some((DialogType::Error, errors.join("\n")));
+        }
+    }
+
+    /// Returns the last known position of the controller assigned to `axis`, in the physical unit of the axis.
+    fn axis_position(&self, axis: Axis) -> Option<f64> {
+        let mc = &self.connd_mtn_ctrlrs[self.mai.get(axis)?];
+        mc.status().position.map(|p| mc.config().steps_to_value(p))
+    }
+
+    /// Returns the position shown as 0 on `axis`, in its physical unit. Without a user zero, that is 0.
+    fn user_zero(&self, axis: Axis) -> f64 {
+        self.mai
+            .get(axis)
+            .and_then(|idx| self.connd_mtn_ctrlrs[idx].user_zero())
+            .unwrap_or(0.0)
+    }
+
+    /// Shows buttons setting the current position of `axis` as its user zero, and clearing it again.
+    ///
+    /// Wavelengths are absolute, so the main drive has no user zero. Its home position is set in the Machine Configuration.
+    fn zero_buttons(&mut self, ui: &mut egui::Ui, axis: Axis) {
+        let position = self.axis_position(axis);
+        let Some(idx) = self.mai.get(axis) else {
+            return;
+        };
+        let mc = &mut self.connd_mtn_ctrlrs[idx];
+
+        if ui.add_enabled(position.is_some(), egui::Button::new("Zero")).on_hover_text("Shows the current position as 0 until cleared.").clicked() {
+            log::info!("User zero of the {} set to {} {}.", axis.as_str(), position.unwrap_or_default(), axis.unit());
+            mc.set_user_zero(position);
+        }
+        if ui.add_enabled(mc.user_zero().is_some(), egui::Button::new("Clear Zero")).clicked() {
+            log::info!("User zero of the {} cleared.", axis.as_str());
+            mc.set_user_zero(None);
+        }
+    }
+
+    /// Copies the last known position of each assigned axis into its manual controls, relative to any user zero.
+    fn update_positions(&mut self) {
+        if let Some(p) = self.axis_position(Axis::MainDrive) {
+            self.pos_curr = p as f32;
+        }
+        if let Some(p) = self.axis_position(Axis::SampleRotation) {
+            self.samp_rot_curr = (p - self.user_zero(Axis::SampleRotation)) as f32;
+        }
+        if let Some(p) = self.axis_position(Axis::SampleAngle) {
+            self.samp_ang_curr = (p - self.user_zero(Axis::SampleAngle)) as f32;
+        }
+        if let Some(p) = self.axis_position(Axis::SampleTranslation) {
+            self.samp_tran_curr = (p - self.user_zero(Axis::SampleTranslation)) as f32;
+        }
+    }
+
+    /// Lists the state of every assigned axis and every detector.
+    fn device_status(&mut self, ui: &mut egui::Ui) {
+        if self.connd_mtn_ctrlrs.is_empty() && self.connd_detectors.is_empty() {
+            ui.label("No devices connected.");
+            return;
+        }
+
+        let error_color = ui.visuals().error_fg_color;
+
+        egui::Grid::new("axis_status").striped(true).show(ui, |ui| {
+            for title in ["Axis", "Device", "Position", "Offsets", "State", "Limits", "Last Error"] {
+                ui.strong(title);
+            }
+            ui.end_row();
+
+            for axis in Axis::ALL {
+                let Some(idx) = self.mai.get(axis) else {
+                    continue;
+                };
+                let mc = &self.connd_mtn_ctrlrs[idx];
+                let status = mc.status();
+                let caps = mc.capabilities();
+                let position = status.position.map(|p| mc.config().steps_to_value(p));
+
+                ui.label(axis.as_str());
+                ui.label(self.mc_name(idx));
+
+                match (position, axis) {
+                    (Some(p), Axis::MainDrive) => ui.label(format!("{:.3} {}", self.x_unit.nm_to_unit(p), self.x_unit.as_str())),
+                    (Some(p), _) => ui.label(format!("{:.3} {}", p, axis.unit())),
+                    (None, _) => ui.label("Unknown"),
+                };
+
+                let mut offsets = format!("Home {} {}", mc.config().offset, axis.unit());
+                if let Some(zero) = mc.user_zero() {
+                    offsets += &format!(", Zero {:.3} {}", zero, axis.unit());
+                }
+                ui.label(offsets);
+
+                match (status.state, status.homed) {
+                    // Only a controller which counts steps or has limit switches can tell that steps were lost.
+                    (MotionState::Idle, _) if status.position_lost && (caps.encoder || caps.limit_switches) => {
+                        ui.colored_label(error_color, "Steps Lost")
+                    }
+                    (MotionState::Idle, false) => ui.label("Not Homed"),
+                    (state, _) => ui.label(state.as_str()),
+                };
+
+                // The switches at the ends of travel matter more than the limits set in software.
+                let switches = status.switches.filter(|_| caps.limit_switches).unwrap_or_default();
+                match (position, mc.config().limits) {
+                    _ if switches.lower => ui.colored_label(error_color, "Lower Switch"),
+                    _ if switches.upper => ui.colored_label(error_color, "Upper Switch"),
+                    _ if switches.home => ui.label("Home Flag"),
+                    (Some(p), Some((min, _))) if p <= min => ui.colored_label(error_color, "At Lower"),
+                    (Some(p), Some((_, max))) if p >= max => ui.colored_label(error_color, "At Upper"),
+                    (_, Some(_)) => ui.label("Within"),
+                    (_, None) => ui.label("None Set"),
+                };
+
+                match status.last_error {
+                    Some(e) => ui.colored_label(error_color, e),
+                    None => ui.label(""),
+                };
+                ui.end_row();
+            }
+        });
+
+        ui.add_space(5.0);
+
+        egui::Grid::new("detector_status").striped(true).show(ui, |ui| {
+            for title in ["Detector", "Device", "Reading", "Range", "Last Error"] {
+                ui.strong(title);
+            }
+            ui.end_row();
+
+            for (i, det) in self.connd_detectors.iter().enumerate() {
+                ui.label(self.det_name(i));
+                ui.label(format!("{} on {}", det.short_name(), det.port_name()));
+
+                match det.latest() {
+                    _ if det.is_overflow() => ui.colored_label(error_color, "Overflow"),
+                    Some(r) => ui.label(format!("{:.4e} pA", r)),
+                    None => ui.label("None"),
+                };
+
+                match det.capabilities().ranging {
+                    true => ui.label(det.range()),
+                    false => ui.label(""),
+                };
+
+                match det.last_error() {
+                    Some(e) => ui.colored_label(error_color, e),
+                    None => ui.label(""),
+                };
+                ui.end_row();
+            }
+        });
+    }
+
+    /// Shows a combo-box which selects the unit of the main drive axis.
+    fn x_unit_combo(&mut self, ui: &mut egui::Ui, id: &str) {
+        let mut unit = self.x_unit;
+
+        egui::ComboBox::from_id_source(id)
+            .selected_text(unit.as_str())
+            .show_ui(ui, |ui| {
+                for u in AxisUnit::ALL {
+                    ui.selectable_value(&mut unit, u, format!("{} ({})", u.as_str(), u.quantity()));
+                }
+            });
+
+        if unit != self.x_unit {
+            self.set_x_unit(unit);
+        }
+    }
+
+    /// Switches the main drive unit, converting the scan definition so that it covers the same range.
+    ///
+    /// The step is converted at the start of the scan, as it is not constant across units that are inverse to wavelength.
+    /// Zero has no inverse, so a step from or to 0 is left as it is when switching to or from such a unit.
+    fn set_x_unit(&mut self, unit: AxisUnit) {
+        let old = self.x_unit;
+        let start = self.scan_start as f64;
+        let stop = start + self.scan_step as f64;
+
+        if (old.is_wavelength() && unit.is_wavelength()) || (start != 0.0 && stop != 0.0) {
+            self.scan_step = (old.convert(stop, unit) - old.convert(start, unit)).abs() as f32;
+        }
+        self.scan_start = old.convert(start, unit) as f32;
+        self.scan_end = old.convert(self.scan_end as f64, unit) as f32;
+        self.x_unit = unit;
+    }
+
+    /// Shows a combo-box which assigns a connected detector to either the sample or the reference role.
+    fn detector_role_combo(&mut self, ui: &mut egui::Ui, id: &str, sample: bool) {
+        let names: Vec<String> = self.connd_detectors.iter().map(|d| d.short_name()).collect();
+        let role = match sample {
+            true => &mut self.dri.sample_idx,
+            false => &mut self.dri.ref_idx,
+        };
+
+        let selected = match *role {
+            Some(idx) => format!("{} {}", idx + 1, names[idx]),
+            None => "None".to_owned(),
+        };
+
+        egui::ComboBox::from_id_source(id)
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(role, None, "None");
+                for (i, name) in names.iter().enumerate() {
+                    ui.selectable_value(role, Some(i), format!("{} {}", i + 1, name));
+                }
+            });
+    }
+
+    /// Reads every connected detector back-to-back so that all channels, in particular the sample and reference, describe the same point.
+    ///
+    /// The main drive's position is taken as last seen, since its driver is busy while it moves.
+    fn record_point(&mut self) {
+        let position = match self.mai.md_idx {
+            Some(_) => match self.axis_position(Axis::MainDrive) {
+                Some(position) => position,
+                None => {
+                    self.pending_dialog = Some((DialogType::Error, "The position of the main drive is not known yet.".to_owned()));
+                    return;
+                }
+            },
+            None => self.pos_curr as f64,
+        };
+
+        let timestamp = middleware::unix_time();
+        let mut readings = Vec::with_capacity(self.connd_detectors.len());
+        for i in 0..self.connd_detectors.len() {
+            let reading = self.connd_detectors[i].driver.lock().unwrap().detect();
+            match reading {
+                Ok(reading) => readings.push(reading),
+                Err(e) => {
+                    self.connd_detectors[i].set_error(e.to_string());
+                    self.pending_dialog = Some((DialogType::Error, format!("Reading {} failed: {}", self.det_name(i), e)));
+                    return;
+                }
+            }
+        }
+
+        self.record(position, &readings, timestamp);
+    }
+
+    /// Adds one reading per detector to the last scan and to the journal.
+    fn record(&mut self, position: f64, readings: &[f64], timestamp: f64) {
+        for (det, &reading) in self.connd_detectors.iter_mut().zip(readings) {
+            det.record(position, reading, timestamp);
+        }
+
+        self.write_journal(|j| j.point(position, timestamp, readings));
+    }
+
+    /// Starts the journal of a newly connected session, replacing that of the previous session.
+    ///
+    /// Once started, detectors connected later are added to it instead, keeping what it already holds.
+    fn start_journal(&mut self) {
+        let detectors: Vec<String> = (self.journaled_detectors..self.connd_detectors.len()).map(|i| self.det_name(i)).collect();
+        let path = journal::default_path();
+
+        if self.journaled_detectors > 0 {
+            for name in detectors {
+                self.write_journal(|j| j.detector(&name));
+            }
+            self.journaled_detectors = self.connd_detectors.len();
+            return;
+        }
+        self.journaled_detectors = self.connd_detectors.len();
+
+        match Journal::create(&path, &detectors) {
+            Ok(journal) => {
+                log::info!("Journaling the session to {}.", path.display());
+                self.journal = Some(journal);
+            }
+            Err(e) => {
+                self.pending_dialog = Some((DialogType::Warn, format!("Could not start the session journal at {}: {}. Scans will not be recoverable after a crash.", path.display(), e)));
+            }
+        }
+
+        // Connecting starts the first scan of every detector.
+        let scans = self.connd_detectors.first().map_or(0, |d| d.get_scans().len());
+        for _ in 0..scans {
+            self.write_journal(|j| j.new_scan());
+        }
+    }
+
+    /// Journaling stops at the first failure, rather than reporting it on every point.
+    fn write_journal(&mut self, write: impl FnOnce(&mut Journal) -> std::io::Result<()>) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+
+        if let Err(e) = write(journal) {
+            self.journal = None;
+            self.pending_dialog = Some((DialogType::Warn, format!("Writing the session journal failed: {}. Scans will not be recoverable after a crash.", e)));
+        }
+    }
+
+    /// Marks the session as closed cleanly.
+    fn close_journal(&mut self) {
+        if let Some(journal) = self.journal.take() {
+            if let Err(e) = journal.close() {
+                log::error!("Could not close the session journal: {}", e);
+            }
+        }
+    }
+
+    fn data_plot(&mut self, ui: &mut egui::Ui) {
+        use egui_plot::{Line, PlotPoints};
+
+        egui::CollapsingHeader::new("Scans").show(ui, |ui| {
+            self.scan_manager(ui);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Show");
+            for i in 0..self.connd_detectors.len() {
+                let name = self.det_name(i);
+                ui.checkbox(&mut self.det_visible[i], name);
+            }
+
+            ui.separator();
+            ui.label("Units");
+            self.x_unit_combo(ui, "Plot Units");
+
+            ui.separator();
+            ui.checkbox(&mut self.plot_follow, "Follow Scan");
+        });
+
+        let unit = self.x_unit;
+        let x_label = unit.axis_label();
+
+        let visible_scans: Vec<usize> = match self.connd_detectors.first() {
+            Some(det) => (0..det.get_scans().len()).filter(|&k| self.is_scan_visible(k)).collect(),
+            None => Vec::new(),
+        };
+
+        // One normalized trace per visible scan, plotted against the sample detector's positions.
+        let ratio = self.dri.pair().map(|(s, r)| {
+            let sample = self.connd_detectors[s].get_scans();
+            let reference = self.connd_detectors[r].get_scans();
+
+            visible_scans
+                .iter()
+                .map(|&k| {
+                    let y = middleware::normalize(&sample[k].readings, &reference[k].readings);
+                    (k, sample[k].positions.iter().map(|&x| unit.nm_to_unit(x)).zip(y).map(|(x, y)| [x, y]).collect::<Vec<[f64; 2]>>())
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // The plots split the remaining height, leaving room for the button below.
+        let num_plots = 1 + ratio.is_some() as usize + self.derived.is_some() as usize;
+        let plot_height = (ui.available_height() - 30.0) / num_plots as f32;
+
+        let plot = egui_plot::Plot::new("test_plot")
+            .legend(egui_plot::Legend::default())
+            .height(plot_height)
+            .y_axis_label("Photocurrent [pA]")
+            .x_axis_label(x_label.clone());
+
+        let names: Vec<String> = (0..self.connd_detectors.len()).map(|i| self.det_name(i)).collect();
+        let follow = self.plot_follow && self.scan.is_some();
+
+        plot.show(ui, |plot_ui| {
+            if follow {
+                plot_ui.set_auto_bounds(egui::Vec2b::new(true, true));
+            }
+
+            for (i, det) in self.connd_detectors.iter().enumerate() {
+                if !self.det_visible[i] {
+                    continue;
+                }
+
+                let scans = det.get_scans();
+                for &k in visible_scans.iter() {
+                    let points: Vec<[f64; 2]> = scans[k]
+                        .positions
+                        .iter()
+                        .zip(scans[k].readings.iter())
+                        .map(|(&x, &y)| [unit.nm_to_unit(x), y])
+                        .collect();
+
+                    plot_ui.line(
+                        Line::new(PlotPoints::from(decimate(&points, MAX_PLOT_POINTS)))
+                            .name(format!("{} Scan {}", names[i], k + 1)),
+                    );
+                }
+
+                // Mark the newest point of a running scan.
+                if let (true, Some(scan)) = (follow, scans.last()) {
+                    if let (Some(&x), Some(&y)) = (scan.positions.last(), scan.readings.last()) {
+                        plot_ui.points(
+                            egui_plot::Points::new(vec![[unit.nm_to_unit(x), y]])
+                                .radius(4.0)
+                                .name(format!("{} Scan {}", names[i], scans.len())),
+                        );
+                    }
+                }
+            }
+
+            for (data, visible) in self.imported.iter() {
+                if !visible {
+                    continue;
+                }
+
+                for (i, scans) in data.scans.iter().enumerate() {
+                    for (k, scan) in scans.iter().enumerate() {
+                        let points: Vec<[f64; 2]> = scan
+                            .positions
+                            .iter()
+                            .zip(scan.readings.iter())
+                            .map(|(&x, &y)| [unit.nm_to_unit(x), y])
+                            .collect();
+
+                        plot_ui.line(
+                            Line::new(PlotPoints::from(decimate(&points, MAX_PLOT_POINTS)))
+                                .style(egui_plot::LineStyle::dashed_dense())
+                                .name(format!("{}: {} Scan {}", data.name, data.detectors[i], k + 1)),
+                        );
+                    }
+                }
+            }
+
+            if let Some(pos) = self.scan_pos {
+                plot_ui.vline(egui_plot::VLine::new(unit.nm_to_unit(pos)).style(egui_plot::LineStyle::dashed_loose()));
+            }
+        });
+
+        if let Some(ratio) = ratio {
+            egui_plot::Plot::new("ratio_plot")
+                .legend(egui_plot::Legend::default())
+                .height(plot_height)
+                .y_axis_label("Sample / Reference")
+                .x_axis_label(x_label.clone())
+                .show(ui, |plot_ui| {
+                    if follow {
+                        plot_ui.set_auto_bounds(egui::Vec2b::new(true, true));
+                    }
+
+                    for (k, points) in ratio {
+                        plot_ui.line(
+                            Line::new(PlotPoints::from(decimate(&points, MAX_PLOT_POINTS)))
+                                .name(format!("Normalized Scan {}", k + 1)),
+                        );
+                    }
+                });
+        }
+
+        if let Some(derived) = &self.derived {
+            let points = |offset: f64| -> PlotPoints {
+                (0..derived.positions.len())
+                    .map(|i| [unit.nm_to_unit(derived.positions[i]), derived.values[i] + offset * derived.uncertainties[i]])
+                    .collect()
+            };
+
+            egui_plot::Plot::new("derived_plot")
+                .legend(egui_plot::Legend::default())
+                .height(plot_height)
+                .y_axis_label(self.derived_quantity.as_str())
+                .x_axis_label(x_label.clone())
+                .show(ui, |plot_ui| {
+                    plot_ui.line(Line::new(points(0.0)).name(self.derived_quantity.as_str()));
+                    plot_ui.line(Line::new(points(1.0)).style(egui_plot::LineStyle::dashed_dense()).name("±1σ"));
+                    plot_ui.line(Line::new(points(-1.0)).style(egui_plot::LineStyle::dashed_dense()).name("±1σ"));
+                });
+        }
+
+        // TODO: Have this button also push to the data record as well.
+        // TODO: Remove (test for the plot).
+        // TEST: Button that generates random data one float at a time.
+        let moving = self.mai.md_idx.is_some() && self.axis_busy(Axis::MainDrive);
+        ui.vertical(|ui| {
+            if ui
+                .add_enabled(self.scan.is_none() && !moving, egui::Button::new("Generate Random Datapoint"))
+                .clicked()
+            {
+                self.record_point();
+            }
+        });
+    }
+
+    /// Lists the recorded scans so they can be marked as dark, baseline or sample, and derives reflectance or transmittance from them.
+    fn scan_manager(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        for (i, (data, visible)) in self.imported.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.checkbox(visible, &data.name);
+                if ui.button("Close").clicked() {
+                    remove = Some(i);
+                }
+            });
+        }
+        if let Some(i) = remove {
+            self.imported.remove(i);
+        }
+
+        if self.connd_detectors.is_empty() {
+            ui.label("No detectors connected.");
+            return;
+        }
+
+        if ui.button("New Scan").clicked() {
+            self.new_scan();
+        }
+
+        // Scans are recorded by all detectors at once, so the kinds are the same for every detector.
+        let kinds: Vec<ScanKind> = self.connd_detectors[0].get_scans().iter().map(|s| s.kind).collect();
+
+        egui::Grid::new("scan_kinds").striped(true).show(ui, |ui| {
+            for (i, kind) in kinds.iter().enumerate() {
+                let mut sel = *kind;
+
+                let mut visible = self.is_scan_visible(i);
+                if ui.checkbox(&mut visible, format!("Scan {}", i + 1)).changed() {
+                    if self.scan_visible.len() <= i {
+                        self.scan_visible.resize(i + 1, true);
+                    }
+                    self.scan_visible[i] = visible;
+                }
+                ui.label(format!("{} points", self.connd_detectors[0].get_scans()[i].readings.len()));
+                egui::ComboBox::from_id_source(format!("Scan Kind {}", i))
+                    .selected_text(sel.as_str())
+                    .show_ui(ui, |ui| {
+                        for k in ScanKind::ALL {
+                            ui.selectable_value(&mut sel, k, k.as_str());
+                        }
+                    });
+                ui.end_row();
+
+                if sel != *kind {
+                    for det in self.connd_detectors.iter_mut() {
+                        det.set_scan_kind(i, sel);
+                    }
+                    self.write_journal(|j| j.scan_kind(i, sel));
+                }
+            }
+        });
+
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("Derived Quantity")
+                .selected_text(self.derived_quantity.as_str())
+                .show_ui(ui, |ui| {
+                    for q in [DerivedQuantity::Reflectance, DerivedQuantity::Transmittance] {
+                        ui.selectable_value(&mut self.derived_quantity, q, q.as_str());
+                    }
+                });
+            ui.label(format!("Grid Step [{}]", self.x_unit.as_str()));
+            ui.add(egui::DragValue::new(&mut self.derived_step).speed(0.1).clamp_range(0.001..=f64::MAX));
+
+            if ui.button("Compute").clicked() {
+                self.compute_derived();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.derived_path);
+
+            if ui
+                .add_enabled(self.derived.is_some(), egui::Button::new("Export"))
+                .clicked()
+            {
+                self.export_derived();
+            }
+        });
+    }
+
+    fn is_scan_visible(&self, scan_idx: usize) -> bool {
+        self.scan_visible.get(scan_idx).copied().unwrap_or(true)
+    }
+
+    /// Returns the nickname of a detector, or its model if it has none.
+    fn det_name(&self, det_idx: usize) -> String {
+        match self.sel_det_nick.get(det_idx) {
+            Some(nick) if !nick.is_empty() && nick != "None" => nick.clone(),
+            _ => format!("{} {}", det_idx + 1, self.connd_detectors[det_idx].short_name()),
+        }
+    }
+
+    /// Describes the connected devices and the scan settings, for the header of data files.
+    fn metadata(&self) -> Metadata {
+        let mut meta = Metadata::default();
+
+        meta.push("Date", datafile::utc_timestamp());
+        meta.push("Operator", &self.operator);
+        meta.push("Software", format!("MCS v{}", env!("CARGO_PKG_VERSION")));
+        meta.push("Units", self.x_unit.axis_label());
+
+        for (i, mc) in self.connd_mtn_ctrlrs.iter().enumerate() {
+            meta.push(
+                format!("Motion Controller {}", i + 1),
+                format!("{} on {} ({})", mc.long_name(), mc.port_name(), mc.identification()),
+            );
+        }
+
+        if let Some(idx) = self.mai.md_idx {
+            meta.push("Main Drive", format!("Motion Controller {}", idx + 1));
+            let config = self.connd_mtn_ctrlrs[idx].config();
+            meta.push("Main Drive Steps per nm", config.steps_per_value);
+            meta.push("Main Drive Home Position [nm]", config.offset);
+            meta.push("Main Drive Backlash [nm]", config.backlash);
+            meta.push("Main Drive Backlash Mode", config.backlash_mode.as_str());
+            if config.backlash_mode == BacklashMode::Approach {
+                meta.push("Main Drive Approach", config.approach.as_str());
+            }
+        }
+
+        // Where the other axes stood, since they do not move during a scan.
+        for axis in Axis::ALL.into_iter().filter(|&a| a != Axis::MainDrive) {
+            let Some(idx) = self.mai.get(axis) else {
+                continue;
+            };
+            let mc = &self.connd_mtn_ctrlrs[idx];
+            let unit = axis.unit();
+
+            meta.push(axis.as_str(), format!("Motion Controller {}", idx + 1));
+            if let Some(p) = self.axis_position(axis) {
+                meta.push(format!("{} Position [{}]", axis.as_str(), unit), p);
+            }
+            meta.push(format!("{} Home Position [{}]", axis.as_str(), unit), mc.config().offset);
+            if let Some(zero) = mc.user_zero() {
+                meta.push(format!("{} User Zero [{}]", axis.as_str(), unit), zero);
+            }
+        }
+
+        let unit = self.x_unit.as_str();
+        meta.push(format!("Scan Start [{}]", unit), self.scan_start);
+        meta.push(format!("Scan End [{}]", unit), self.scan_end);
+        meta.push(format!("Scan Step [{}]", unit), self.scan_step);
+        meta.push("Scan Repeats", self.scan_repeats);
+
+        for (i, det) in self.connd_detectors.iter().enumerate() {
+            meta.push(
+                format!("Detector {}", i + 1),
+                format!("{} on {} ({})", det.long_name(), det.port_name(), det.identification()),
+            );
+
+            let role = match (self.dri.sample_idx, self.dri.ref_idx) {
+                (Some(s), _) if s == i => "Sample",
+                (_, Some(r)) if r == i => "Reference",
+                _ => "None",
+            };
+            meta.push(format!("Detector {} Role", i + 1), role);
+
+            let dark = analysis::Dark::from_scans(det.get_scans());
+            meta.push(
+                format!("Detector {} Dark [pA]", i + 1),
+                format!("{} ± {}", dark.level, dark.uncertainty),
+            );
+        }
+
+        meta
+    }
+
+    /// Saves every scan of every connected detector to `data_path`.
+    ///
+    /// Paths ending in .json are written as a session archive, anything else as delimited text.
+    fn export_scans(&mut self) {
+        let data = DataSet {
+            name: self.data_path.clone(),
+            metadata: self.metadata(),
+            detectors: (0..self.connd_detectors.len()).map(|i| self.det_name(i)).collect(),
+            scans: self.connd_detectors.iter().map(|d| d.get_scans().clone()).collect(),
+            sample_ref: self.dri.pair(),
+        };
+
+        let result = match is_archive(&self.data_path) {
+            true => datafile::archive::write(&self.data_path, &data),
+            false => datafile::delimited::write(&self.data_path, &data, self.x_unit).map_err(|e| e.to_string()),
+        };
+
+        match result {
+            Ok(_) => log::info!("Exported scans to {}.", self.data_path),
+            Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Export failed: {}", e))),
+        }
+    }
+
+    /// Loads scans saved by `export_scans` from `data_path` and overlays them on the plot.
+    fn import_scans(&mut self) {
+        let result = match is_archive(&self.data_path) {
+            true => datafile::archive::read(&self.data_path),
+            false => datafile::delimited::read(&self.data_path),
+        };
+
+        match result {
+            Ok(data) => {
+                log::info!("Opened {} scans from {}.", data.scans.first().map_or(0, |s| s.len()), self.data_path);
+                self.imported.push((data, true));
+            }
+            Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Could not open {}: {}", self.data_path, e))),
+        }
+    }
+
+    /// Starts a new scan on every detector. An empty last scan is reused rather than left behind.
+    fn new_scan(&mut self) {
+        let last_empty = self
+            .connd_detectors
+            .first()
+            .is_some_and(|d| d.get_scans().last().is_some_and(|s| s.readings.is_empty()));
+
+        if last_empty {
+            return;
+        }
+
+        for det in self.connd_detectors.iter_mut() {
+            det.new_scan();
+        }
+        self.write_journal(|j| j.new_scan());
+    }
+
+    /// Starts scanning the main drive over the range set in the Scanning Control.
+    fn start_scan(&mut self, ctx: &egui::Context) {
+        let Some(md_idx) = self.mai.md_idx else {
+            return;
+        };
+
+        let md = &self.connd_mtn_ctrlrs[md_idx];
+        let def = ScanDefinition {
+            positions: ScanDefinition::positions(
+                self.scan_start as f64,
+                self.scan_end as f64,
+                self.scan_step as f64,
+                self.x_unit,
+            ),
+            repeats: self.scan_repeats,
+            axis: md.config().clone(),
+        };
+
+        if let Err(e) = def.positions.iter().try_for_each(|&p| def.axis.check_limits(p)) {
+            self.pending_dialog = Some((DialogType::Error, format!("The scan leaves the limits of the main drive: {}", e)));
+            return;
+        }
+
+        let detectors = self.connd_detectors.iter().map(|d| d.driver.clone()).collect();
+
+        self.scan = Some(scan::start(def, md.driver.clone(), md.shared_status(), detectors, ctx.clone()));
+    }
+
+    /// Takes in everything the running scan has measured since the last frame.
+    ///
+    /// Called every frame regardless of which page or tab is shown, so no data is held up.
+    fn poll_scan(&mut self) {
+        let Some(scan) = &self.scan else {
+            return;
+        };
+
+        let mut events = Vec::new();
+        loop {
+            match scan.events.try_recv() {
+                Ok(event) => {
+                    let last = matches!(event, ScanEvent::Finished | ScanEvent::Failed(_));
+                    events.push(event);
+                    if last {
+                        break;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                // The thread only ends without saying so if it panicked.
+                Err(TryRecvError::Disconnected) => {
+                    events.push(ScanEvent::Failed("The scan stopped unexpectedly.".to_owned()));
+                    break;
+                }
+            }
+        }
+        let mut done = false;
+
+        for event in events {
+            match event {
+                ScanEvent::NewScan => self.new_scan(),
+                ScanEvent::Moving { position } => self.scan_pos = Some(position),
+                ScanEvent::Point { position, readings, timestamp } => {
+                    self.record(position, &readings, timestamp);
+                    self.pos_curr = position as f32;
+                }
+                ScanEvent::Finished => done = true,
+                ScanEvent::Failed(e) => {
+                    self.pending_dialog = Some((DialogType::Error, format!("Scan failed: {}", e)));
+                    done = true;
+                }
+            }
+        }
+
+        if done {
+            self.scan = None;
+            self.scan_pos = None;
+        }
+    }
+
+    /// Computes I / I0 of the sample detector, normalized to the reference detector if one is assigned.
+    ///
+    /// The common grid is evenly spaced in the selected unit. The result is stored in nm like all positions.
+    fn compute_derived(&mut self) {
+        let unit = self.x_unit;
+        let in_unit = |scans: &Vec<Scan>| -> Vec<Scan> {
+            scans
+                .iter()
+                .map(|s| Scan {
+                    positions: s.positions.iter().map(|&p| unit.nm_to_unit(p)).collect(),
+                    ..s.clone()
+                })
+                .collect()
+        };
+
+        let sample = in_unit(self.connd_detectors[self.dri.sample_idx.unwrap_or(0)].get_scans());
+        let reference = self.dri.pair().map(|(_, r)| in_unit(self.connd_detectors[r].get_scans()));
+
+        match analysis::derive(&sample, reference.as_deref(), self.derived_step) {
+            Ok(mut spectrum) => {
+                spectrum.positions.iter_mut().for_each(|p| *p = unit.unit_to_nm(*p));
+                self.derived = Some(spectrum);
+            }
+            Err(e) => {
+                self.derived = None;
+                self.pending_dialog = Some((DialogType::Error, e));
+            }
+        }
+    }
+
+    fn export_derived(&mut self) {
+        let Some(derived) = &self.derived else {
+            return;
+        };
+
+        let header = vec![
+            format!("{} (I / I0, dark subtracted)", self.derived_quantity.as_str()),
+            format!("Normalized to reference detector: {}", self.dri.pair().is_some()),
+            format!("{},{},Uncertainty", self.x_unit.axis_label(), self.derived_quantity.as_str()),
+        ];
+
+        let mut derived = derived.clone();
+        derived.positions.iter_mut().for_each(|p| *p = self.x_unit.nm_to_unit(*p));
+
+        match derived.write_csv(&self.derived_path, &header) {
+            Ok(_) => log::info!("Exported {} to {}.", self.derived_quantity.as_str(), self.derived_path),
+            Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Export failed: {}", e))),
+        }
+    }
+
+    /// Assigns the connected motion controllers to the axes of the machine and calibrates each of them.
+    ///
+    /// Nothing may change while scanning, as the scan works with the assignments and calibration it started with.
+    fn machine_config(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Machine Configuration");
+
+        ui.add_space(15.0);
+
+        ui.add_enabled_ui(self.scan.is_none(), |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Profile");
+                ui.text_edit_singleline(&mut self.profile.name);
+                ui.label("File");
+                ui.text_edit_singleline(&mut self.profile_path);
+
+                if ui.button("Export").on_hover_text("Save the devices, axis assignments and calibrations.").clicked() {
+                    self.export_profile();
+                }
+                if ui.button("Import").on_hover_text("Restore the devices, axis assignments and calibrations.").clicked() {
+                    self.import_profile();
+                }
+            });
+        });
+
+        ui.add_space(15.0);
+
+        if self.connd_mtn_ctrlrs.is_empty() {
+            ui.label("No motion controllers connected.");
+            return;
+        }
+
+        ui.add_enabled_ui(self.scan.is_none(), |ui| {
+            ui.label("Axis Assignment");
+
+            egui::Grid::new("axis_assignment").striped(true).show(ui, |ui| {
+                for axis in Axis::ALL {
+                    ui.label(axis.as_str());
+
+                    let mut sel = self.mai.get(axis);
+                    egui::ComboBox::from_id_source(format!("Axis {}", axis.as_str()))
+                        .selected_text(sel.map_or("Unassigned".to_owned(), |i| self.mc_name(i)))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut sel, None, "Unassigned");
+                            for i in 0..self.connd_mtn_ctrlrs.len() {
+                                ui.selectable_value(&mut sel, Some(i), self.mc_name(i));
+                            }
+                        });
+                    ui.end_row();
+
+                    if sel != self.mai.get(axis) {
+                        // A controller can only drive one axis.
+                        if let Some(other) = sel.and_then(|i| self.mai.axis_of(i)) {
+                            *self.mai.get_mut(other) = None;
+                        }
+                        *self.mai.get_mut(axis) = sel;
+                    }
+                }
+            });
+
+            ui.add_space(15.0);
+            ui.label("Calibration");
+
+            for i in 0..self.connd_mtn_ctrlrs.len() {
+                let axis = self.mai.axis_of(i);
+                let unit = axis.map_or("unit", |a| a.unit()).to_owned();
+                let title = format!("{} - {}", self.mc_name(i), axis.map_or("Unassigned", |a| a.as_str()));
+                let caps = self.connd_mtn_ctrlrs[i].capabilities();
+                let config = self.connd_mtn_ctrlrs[i].config_mut();
+
+                egui::CollapsingHeader::new(title)
+                    .id_source(format!("Calibration {}", i))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new(format!("calibration_{}", i)).show(ui, |ui| {
+                            ui.label(format!("Steps per {}", unit));
+                            ui.add(egui::DragValue::new(&mut config.steps_per_value).speed(1.0).clamp_range(1e-6..=f64::MAX));
+                            ui.end_row();
+
+                            ui.label(format!("Home Position [{}]", unit)).on_hover_text("The value on this axis when the controller is homed.");
+                            ui.add(egui::DragValue::new(&mut config.offset).speed(0.1));
+                            ui.end_row();
+
+                            let mut limited = config.limits.is_some();
+                            ui.checkbox(&mut limited, format!("Limits [{}]", unit));
+                            if limited != config.limits.is_some() {
+                                config.limits = limited.then_some((config.offset, config.offset));
+                            }
+                            ui.horizontal(|ui| {
+                                if let Some((min, max)) = &mut config.limits {
+                                    ui.add(egui::DragValue::new(min).speed(0.1).clamp_range(f64::MIN..=*max));
+                                    ui.label("to");
+                                    ui.add(egui::DragValue::new(max).speed(0.1).clamp_range(*min..=f64::MAX));
+                                }
+                            });
+                            ui.end_row();
+
+                            ui.label(format!("Jog Distances [{}]", unit)).on_hover_text("Fine and coarse.");
+                            ui.horizontal(|ui| {
+                                ui.add(egui::DragValue::new(&mut config.jog_fine).speed(0.1).clamp_range(0.0..=f64::MAX));
+                                ui.add(egui::DragValue::new(&mut config.jog_coarse).speed(0.1).clamp_range(0.0..=f64::MAX));
+                            });
+                            ui.end_row();
+
+                            ui.label(format!("Jog Speed [{}/s]", unit)).on_hover_text("When holding a jog button down.");
+                            ui.add(egui::DragValue::new(&mut config.jog_speed).speed(0.1).clamp_range(1e-6..=f64::MAX));
+                            ui.end_row();
+
+                            if caps.encoder {
+                                ui.label(format!("Position Tolerance [{}]", unit))
+                                    .on_hover_text("How far the controller's count may stray from where the axis was sent before steps count as lost.");
+                                ui.add(egui::DragValue::new(&mut config.tolerance).speed(0.1).clamp_range(0.0..=f64::MAX));
+                                ui.end_row();
+                            }
+
+                            ui.label(format!("Backlash [{}]", unit)).on_hover_text("Play in the drive train, taken out of manual moves and scans alike.");
+                            ui.add(egui::DragValue::new(&mut config.backlash).speed(0.1).clamp_range(0.0..=f64::MAX));
+                            ui.end_row();
+
+                            ui.label("Backlash Mode");
+                            egui::ComboBox::from_id_source(format!("Backlash Mode {}", i))
+                                .selected_text(config.backlash_mode.as_str())
+                                .show_ui(ui, |ui| {
+                                    for m in BacklashMode::ALL {
+                                        ui.selectable_value(&mut config.backlash_mode, m, m.as_str());
+                                    }
+                                });
+                            ui.end_row();
+
+                            ui.label("Approach").on_hover_text("Moves coming the other way overshoot the target by the backlash and return.");
+                            ui.add_enabled_ui(config.backlash_mode == BacklashMode::Approach, |ui| {
+                                egui::ComboBox::from_id_source(format!("Approach {}", i))
+                                    .selected_text(config.approach.as_str())
+                                    .show_ui(ui, |ui| {
+                                        for a in Approach::ALL {
+                                            ui.selectable_value(&mut config.approach, a, a.as_str());
+                                        }
+                                    });
+                            });
+                            ui.end_row();
+
+                            ui.label("Home");
+                            egui::ComboBox::from_id_source(format!("Home Behavior {}", i))
+                                .selected_text(config.home.as_str())
+                                .show_ui(ui, |ui| {
+                                    for h in HomeBehavior::ALL {
+                                        ui.selectable_value(&mut config.home, h, h.as_str());
+                                    }
+                                });
+                            ui.end_row();
+                        });
+                    });
+            }
+
+            ui.add_space(15.0);
+            ui.label("Driver Settings");
+
+            for i in 0..self.connd_mtn_ctrlrs.len() {
+                self.driver_settings(ui, i);
+            }
+        });
+    }
+
+    /// Shows the settings particular to the model of the controller at `mc_idx`. They cannot be changed while it moves.
+    fn driver_settings(&mut self, ui: &mut egui::Ui, mc_idx: usize) {
+        let mc = &self.connd_mtn_ctrlrs[mc_idx];
+        if mc.settings().is_empty() {
+            return;
+        }
+
+        let idle = mc.status().state == MotionState::Idle;
+        let mut changed = None;
+
+        egui::CollapsingHeader::new(self.mc_name(mc_idx))
+            .id_source(format!("Driver Settings {}", mc_idx))
+            .show(ui, |ui| {
+                ui.add_enabled_ui(idle, |ui| {
+                    changed = settings_grid(ui, &format!("driver_settings_{}", mc_idx), mc.settings());
+                });
+            });
+
+        if let Some((name, value)) = changed {
+            if let Err(e) = self.connd_mtn_ctrlrs[mc_idx].set_setting(name, value) {
+                self.pending_dialog = Some((DialogType::Error, format!("Setting the {} of {} failed: {}", name, self.mc_name(mc_idx), e)));
+            }
+        }
+    }
+
+    /// Describes the current setup, from the Device Manager selections and the connected devices.
+    ///
+    /// Devices which are selected but not connected keep their setup from the last loaded profile.
+    fn machine_profile(&self) -> MachineProfile {
+        let get = |v: &Vec<String>, i: usize| v.get(i).cloned().unwrap_or_else(|| "None".to_owned());
+
+        let motion_controllers = (0..self.num_mc_devs)
+            .map(|i| {
+                let port_name = get(&self.sel_mc_port, i);
+                let saved = self.profile.motion_controller(&port_name);
+                let connected = self.connd_mtn_ctrlrs.iter().position(|mc| mc.port_name() == port_name);
+
+                MotionControlSetup {
+                    model: get(&self.sel_mc_model, i),
+                    serial_number: self.sel_mc_serial.get(i).cloned().flatten(),
+                    nickname: get(&self.sel_mc_nick, i),
+                    axis: match connected {
+                        Some(idx) => self.mai.axis_of(idx),
+                        None => saved.and_then(|s| s.axis),
+                    },
+                    config: match connected {
+                        Some(idx) => self.connd_mtn_ctrlrs[idx].config().clone(),
+                        None => saved.map(|s| s.config.clone()).unwrap_or_default(),
+                    },
+                    port_name,
+                }
+            })
+            .collect();
+
+        let detectors = (0..self.num_det_devs)
+            .map(|i| {
+                let port_name = get(&self.sel_det_port, i);
+                let saved = self.profile.detector(&port_name);
+                let connected = self.connd_detectors.iter().position(|det| det.port_name() == port_name);
+
+                DetectorSetup {
+                    model: get(&self.sel_det_model, i),
+                    serial_number: self.sel_det_serial.get(i).cloned().flatten(),
+                    nickname: get(&self.sel_det_nick, i),
+                    role: match connected {
+                        Some(idx) if self.dri.sample_idx == Some(idx) => Some(DetectorRole::Sample),
+                        Some(idx) if self.dri.ref_idx == Some(idx) => Some(DetectorRole::Reference),
+                        Some(_) => None,
+                        None => saved.and_then(|s| s.role),
+                    },
+                    settings: match connected {
+                        Some(idx) => self.connd_detectors[idx].saved_settings().to_vec(),
+                        None => saved.map(|s| s.settings.clone()).unwrap_or_default(),
+                    },
+                    port_name,
+                }
+            })
+            .collect();
+
+        MachineProfile {
+            name: self.profile.name.clone(),
+            motion_controllers,
+            detectors,
+        }
+    }
+
+    /// Fills in the Device Manager from `profile`, and sets up those of its devices which are already connected.
+    fn apply_profile(&mut self, profile: MachineProfile) {
+        self.num_mc_devs = profile.motion_controllers.len().clamp(1, 10);
+        self.sel_mc_port = profile.motion_controllers.iter().map(|s| s.port_name.clone()).collect();
+        self.sel_mc_model = profile.motion_controllers.iter().map(|s| s.model.clone()).collect();
+        self.sel_mc_nick = profile.motion_controllers.iter().map(|s| s.nickname.clone()).collect();
+        self.sel_mc_serial = profile.motion_controllers.iter().map(|s| s.serial_number.clone()).collect();
+
+        self.num_det_devs = profile.detectors.len().clamp(1, 2);
+        self.sel_det_port = profile.detectors.iter().map(|s| s.port_name.clone()).collect();
+        self.sel_det_model = profile.detectors.iter().map(|s| s.model.clone()).collect();
+        self.sel_det_nick = profile.detectors.iter().map(|s| s.nickname.clone()).collect();
+        self.sel_det_serial = profile.detectors.iter().map(|s| s.serial_number.clone()).collect();
+
+        self.profile = profile;
+
+        if !self.connd_mtn_ctrlrs.is_empty() || !self.connd_detectors.is_empty() {
+            self.mai = MovementAxesIndices::default();
+            self.dri = DetectorRoleIndices::default();
+            self.assign_from_profile(&self.profile.clone());
+        }
+    }
+
+    /// Looks up the current port of each selected device bound to a USB serial number, as port names may change.
+    fn resolve_ports(&mut self) -> MissingDevices {
+        let ports = serialport::available_ports().unwrap_or_default();
+        let mut missing = MissingDevices::default();
+
+        let mut resolve = |port_name: &mut String, serial_number: &Option<String>, model: &str| -> bool {
+            let Some(sn) = serial_number else {
+                return true;
+            };
+
+            match profile::find_port(&ports, sn, port_name) {
+                Some(current) => {
+                    if *port_name != current {
+                        log::info!("{} with serial number {} moved from {} to {}.", model, sn, port_name, current);
+                        *port_name = current;
+                    }
+                    true
+                }
+                None => {
+                    missing.descriptions.push(format!("{} with serial number {}, last on {}", model, sn, port_name));
+                    false
+                }
+            }
+        };
+
+        for i in 0..self.num_mc_devs.min(self.sel_mc_port.len()) {
+            let model = self.sel_mc_model.get(i).cloned().unwrap_or_default();
+            let serial_number = self.sel_mc_serial.get(i).cloned().flatten();
+
+            if !resolve(&mut self.sel_mc_port[i], &serial_number, &model) {
+                missing.motion_controllers.push(i);
+            }
+        }
+
+        for i in 0..self.num_det_devs.min(self.sel_det_port.len()) {
+            let model = self.sel_det_model.get(i).cloned().unwrap_or_default();
+            let serial_number = self.sel_det_serial.get(i).cloned().flatten();
+
+            if !resolve(&mut self.sel_det_port[i], &serial_number, &model) {
+                missing.detectors.push(i);
+            }
+        }
+
+        // The saved setups are looked up by port.
+        for setup in self.profile.motion_controllers.iter_mut() {
+            if let Some(port_name) = setup.serial_number.as_deref().and_then(|sn| profile::find_port(&ports, sn, &setup.port_name)) {
+                setup.port_name = port_name;
+            }
+        }
+        for setup in self.profile.detectors.iter_mut() {
+            if let Some(port_name) = setup.serial_number.as_deref().and_then(|sn| profile::find_port(&ports, sn, &setup.port_name)) {
+                setup.port_name = port_name;
+            }
+        }
+
+        missing
+    }
+
+    /// Assigns and calibrates the connected devices as set up in `profile`, where their roles are still free.
+    fn assign_from_profile(&mut self, profile: &MachineProfile) {
+        for (i, mc) in self.connd_mtn_ctrlrs.iter_mut().enumerate() {
+            let Some(setup) = profile.motion_controller(&mc.port_name()) else {
+                continue;
+            };
 
-    fn data_plot(&mut self, ui: &mut egui::Ui) {
-        use egui_plot::{Line, PlotPoints};
-        let n = 128;
-        
-        let line = Line::new(
-            (0..=n)
-                .map(|i| {
-                    use std::f64::consts::TAU;
-                    let x = egui::remap(i as f64, 0.0..=n as f64, -TAU..=TAU);
-                    [x, 10.0 * gaussian(x)]
-                })
-                .collect::<PlotPoints>(),
-        );
-        
-        let plot = egui_plot::Plot::new("test_plot")
-            .legend(egui_plot::Legend::default())
-            .y_axis_label("Photocurrent [pA]")
-            .x_axis_label("Wavelength [nm]");
+            *mc.config_mut() = setup.config.clone();
 
-        plot.show(ui, |plot_ui| {
-            for i in 0..self.detector_data.len() {
-                // TODO: Some sort of show/dont show condition.
+            if let Some(axis) = setup.axis {
+                if self.mai.get(axis).is_none() && self.mai.axis_of(i).is_none() {
+                    *self.mai.get_mut(axis) = Some(i);
+                }
+            }
+        }
 
-                plot_ui.line(Line::new(
-                    self.detector_data[i]
-                        .iter()
-                        .enumerate()
-                        .map(|(i, &y)| [i as f64, y])
-                        .collect::<PlotPoints>(),
-                ));
+        for (i, det) in self.connd_detectors.iter().enumerate() {
+            match profile.detector(&det.port_name()).and_then(|s| s.role) {
+                Some(DetectorRole::Sample) if self.dri.sample_idx.is_none() => self.dri.sample_idx = Some(i),
+                Some(DetectorRole::Reference) if self.dri.ref_idx.is_none() => self.dri.ref_idx = Some(i),
+                _ => {}
+            }
+        }
+    }
+
+    fn export_profile(&mut self) {
+        match self.machine_profile().write(&self.profile_path) {
+            Ok(_) => log::info!("Exported machine profile \"{}\" to {}.", self.profile.name, self.profile_path),
+            Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Export failed: {}", e))),
+        }
+    }
 
-                // Line::new(self.detector_data)
-                // plot_ui.line(&line);
+    fn import_profile(&mut self) {
+        match MachineProfile::read(&self.profile_path) {
+            Ok(profile) => {
+                log::info!("Imported machine profile \"{}\" from {}.", profile.name, self.profile_path);
+                self.apply_profile(profile);
             }
+            Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Could not open {}: {}", self.profile_path, e))),
+        }
+    }
+
+    /// Returns e.g. "MC 1: MP 789A-4 on COM3", to tell the motion controllers apart.
+    fn mc_name(&self, mc_idx: usize) -> String {
+        let mc = &self.connd_mtn_ctrlrs[mc_idx];
+        format!("MC {}: {} on {}", mc_idx + 1, mc.short_name(), mc.port_name())
+    }
+
+    /// Shows what has been logged this session, e.g. device commands, movements, scans and errors.
+    fn data_log(&mut self, ui: &mut egui::Ui) {
+        self.log_filtered.update(self.log_level, &self.log_source, &self.log_search);
+
+        ui.horizontal(|ui| {
+            ui.label("Level");
+            egui::ComboBox::from_id_source("Log Level")
+                .selected_text(self.log_level.as_str())
+                .show_ui(ui, |ui| {
+                    for level in [log::LevelFilter::Error, log::LevelFilter::Warn, log::LevelFilter::Info, log::LevelFilter::Debug] {
+                        ui.selectable_value(&mut self.log_level, level, level.as_str());
+                    }
+                });
+
+            ui.label("Source");
+            egui::ComboBox::from_id_source("Log Source")
+                .selected_text(self.log_source.as_deref().unwrap_or("All"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.log_source, None, "All");
+                    for source in self.log_filtered.sources.iter() {
+                        ui.selectable_value(&mut self.log_source, Some(source.clone()), source);
+                    }
+                });
+
+            ui.label("Search");
+            ui.text_edit_singleline(&mut self.log_search);
         });
 
-        // TODO: Have this button also push to the data record as well.
-        // TODO: Remove (test for the plot).
-        // TEST: Button that generates random data one float at a time.
-        ui.vertical(|ui| {
-            if ui.button("Generate Random Datapoint").clicked() {
-                for i in 0..self.detector_data.len() {
-                    self.detector_data[i].push(self.connd_detectors[i].detect());
-                    // TODO: Repaint done when scan is active... use scan_active bool or something.
-                    ui.ctx().request_repaint();
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.log_path);
+
+            if ui.button("Save").on_hover_text("Save the whole session log, regardless of the filters.").clicked() {
+                match event_log::save(&self.log_path) {
+                    Ok(_) => log::info!("Saved the session log to {}.", self.log_path),
+                    Err(e) => self.pending_dialog = Some((DialogType::Error, format!("Could not save the log: {}", e))),
                 }
             }
         });
-    }
 
-    fn data_log(&mut self, ui: &mut egui::Ui) {
-        ui.label("This is the data log tab.");
+        ui.separator();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .stick_to_bottom(true)
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::auto())
+            .column(egui_extras::Column::remainder())
+            .header(row_height, |mut header| {
+                for title in ["Time (UTC)", "Level", "Source", "Message"] {
+                    header.col(|ui| {
+                        ui.strong(title);
+                    });
+                }
+            })
+            .body(|body| {
+                let matches = &self.log_filtered.matches;
+
+                // Only the rows in view are drawn, straight from the log. Entries dropped since the update are left blank.
+                event_log::with_entries(|entries, dropped| {
+                    body.rows(row_height, matches.len(), |mut row| {
+                        let Some(entry) = matches[row.index()].checked_sub(dropped).and_then(|i| entries.get(i)) else {
+                            return;
+                        };
+
+                        row.col(|ui| {
+                            ui.monospace(event_log::time_of_day(entry.timestamp));
+                        });
+                        row.col(|ui| {
+                            let color = match entry.level {
+                                log::Level::Error => ui.visuals().error_fg_color,
+                                log::Level::Warn => ui.visuals().warn_fg_color,
+                                _ => ui.visuals().text_color(),
+                            };
+                            ui.colored_label(color, entry.level.as_str());
+                        });
+                        row.col(|ui| {
+                            ui.label(&entry.source);
+                        });
+                        row.col(|ui| {
+                            ui.label(&entry.message);
+                        });
+                    });
+                });
+            });
     }
 
     fn default_tab(&mut self, ui: &mut egui::Ui) {
@@ -330,19 +1890,23 @@ struct Mcs {
     tabs: McsTabs,
     tree: DockState<String>,
 
-    devices_loading_progress: f32,
-    devices_loading: bool,
-
-    // TODO: Move to a middleware
-    mtn_ctrl_models: Vec<String>, // Supported models
-    det_models: Vec<String>, // Supported models
-
-    first_time: bool,
-    connd_mtn_ctrlrs: Vec<MotionController>,
-
-    mai: MovementAxesIndices,
-
-
+    // Devices being connected in the background, and how far along that is.
+    connecting: Option<Receiver<ConnectEvent>>,
+    connect_progress: f32,
+    connect_status: String,
+    connect_errors: Vec<String>,
+    connect_profile: MachineProfile, // The setup when connecting was started, to assign the devices with.
+
+    // Ports being probed for instruments in the background.
+    detecting: Option<Receiver<DetectEvent>>,
+    detect_progress: f32,
+    detect_status: String,
+    detected: Vec<FoundDevice>,
+
+    // Scans of a session which did not close cleanly, offered for recovery at startup.
+    recovered: Option<DataSet>,
+    recovered_journal: Option<PathBuf>, // Moved aside from the live journal, to remove once the user decides.
+    recovered_path: String,
 }
 
 impl Default for Mcs {
@@ -350,7 +1914,7 @@ impl Default for Mcs {
         let mut tree = DockState::new(vec!["Device Controls".to_owned()]);
 
         // You can modify the tree before constructing the dock
-        let [a, b] = tree.main_surface_mut().split_right(
+        let [a, _] = tree.main_surface_mut().split_right(
             NodeIndex::root(),
             0.5,
             vec!["Data Plot".to_owned()],
@@ -368,12 +1932,16 @@ impl Default for Mcs {
             sel_mc_port: Vec::new(),
             sel_det_port: Vec::new(),
 
+            sel_mc_serial: Vec::new(),
+            sel_det_serial: Vec::new(),
+
             sel_mc_model: Vec::new(),
             sel_det_model: Vec::new(),
 
             sel_mc_nick: Vec::new(),
             sel_det_nick: Vec::new(),
 
+            x_unit: AxisUnit::Nanometre,
             pos_target: 0.0,
             pos_curr: 0.0,
             scan_start: 0.0,
@@ -392,9 +1960,42 @@ impl Default for Mcs {
             samp_scan_step: 0.0,
             samp_scan_repeats: 0,
 
-            detector_data: Vec::new(),
+            det_visible: Vec::new(),
+            scan_visible: Vec::new(),
 
+            plot_follow: true,
+
+            operator: String::new(),
+            data_path: "scans.csv".to_owned(),
+            imported: Vec::new(),
+            journal: None,
+            journaled_detectors: 0,
+
+            scan: None,
+            scan_pos: None,
+            motions: Vec::new(),
+            jog_axis: None,
+
+            connd_mtn_ctrlrs: Vec::new(),
             connd_detectors: Vec::new(),
+            mai: MovementAxesIndices::default(),
+            dri: DetectorRoleIndices::default(),
+
+            derived_quantity: DerivedQuantity::Reflectance,
+            derived_step: 1.0,
+            derived_path: "derived.csv".to_owned(),
+            derived: None,
+
+            profile: MachineProfile::default(),
+            profile_path: "machine.json".to_owned(),
+
+            log_level: log::LevelFilter::Info,
+            log_source: None,
+            log_search: String::new(),
+            log_path: format!("mcs_{}.log", datafile::utc_timestamp().replace([':', '-'], "")),
+            log_filtered: event_log::Filtered::default(),
+
+            pending_dialog: None,
         };
 
         Self {
@@ -409,28 +2010,97 @@ impl Default for Mcs {
             search_ports: true,
             ports: Ok(Vec::new()),
 
-            devices_loading_progress: 0.0,
-            devices_loading: false,
+            connecting: None,
+            connect_progress: 0.0,
+            connect_status: String::new(),
+            connect_errors: Vec::new(),
+            connect_profile: MachineProfile::default(),
+
+            detecting: None,
+            detect_progress: 0.0,
+            detect_status: String::new(),
+            detected: Vec::new(),
+
+            recovered: None,
+            recovered_journal: None,
+            recovered_path: "recovered.json".to_owned(),
 
             tabs,
             tree,
-            
-            mtn_ctrl_models: vec!["MC1".to_owned(), "MC2".to_owned(), "MC3".to_owned()],
-            det_models: vec!["De1".to_owned(), "De2".to_owned(), "De3".to_owned()],
 
-            first_time: true,
-            connd_mtn_ctrlrs: Vec::new(),
+            // connd_mtn_ctrlrs: Vec::new(),
             // connd_detectors: Vec::new(),
-            mai: MovementAxesIndices::default(),
-
-
         }
     }
 }
 
+// UI preferences restored on launch. The machine profile and dock layout are stored separately, so that each is
+// restored even if another can no longer be read.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Preferences {
+    dark_mode: bool,
+    x_unit: AxisUnit,
+    plot_follow: bool,
+    operator: String,
+    data_path: String,
+}
+
+const PROFILE_KEY: &str = "machine_profile";
+const PREFERENCES_KEY: &str = "preferences";
+const DOCK_KEY: &str = "dock";
+
 impl Mcs {
+    /// Restores the machine profile, preferences and dock layout of the last run, where available.
+    fn new(cc: &eframe::CreationContext) -> Self {
+        let mut mcs = Mcs::default();
+
+        if let Some((data, journal)) = journal::set_aside(&journal::default_path()) {
+            mcs.recovered = Some(data);
+            mcs.recovered_journal = journal;
+        }
+
+        let Some(storage) = cc.storage else {
+            return mcs;
+        };
+
+        if let Some(profile) = eframe::get_value::<MachineProfile>(storage, PROFILE_KEY) {
+            mcs.tabs.apply_profile(profile);
+
+            let missing = mcs.tabs.resolve_ports();
+            if !missing.descriptions.is_empty() {
+                mcs.dialog(DialogType::Warn, &format!("These instruments of the machine profile are not plugged in:\n{}", missing.descriptions.join("\n")));
+            }
+        }
+
+        if let Some(prefs) = eframe::get_value::<Preferences>(storage, PREFERENCES_KEY) {
+            mcs.dark_mode = prefs.dark_mode;
+            mcs.tabs.x_unit = prefs.x_unit;
+            mcs.tabs.plot_follow = prefs.plot_follow;
+            mcs.tabs.operator = prefs.operator;
+            mcs.tabs.data_path = prefs.data_path;
+
+            cc.egui_ctx.set_visuals(match mcs.dark_mode {
+                true => Visuals::dark(),
+                false => Visuals::light(),
+            });
+        }
+
+        if let Some(tree) = eframe::get_value::<DockState<String>>(storage, DOCK_KEY) {
+            mcs.tree = tree;
+        }
+
+        mcs
+    }
+
     /// Instantiates an instance of a modal dialog window.
     fn dialog(&mut self, dialog_type: DialogType, message: &str) {
+        match dialog_type {
+            DialogType::Debug => log::debug!("{}", message),
+            DialogType::Info => log::info!("{}", message),
+            DialogType::Warn => log::warn!("{}", message),
+            DialogType::Error => log::error!("{}", message),
+        }
+
         match self.tabs.modal_active {
             true => {
                 println!(
@@ -447,6 +2117,269 @@ impl Mcs {
         }
     }
 
+    /// Connects the devices selected in the Device Manager in the background. See `poll_connect`.
+    ///
+    /// Devices bound to a USB serial number are connected on whichever port they are on now, and skipped with a warning
+    /// if they are not plugged in.
+    fn connect_devices(&mut self, ctx: &egui::Context) {
+        let missing = self.tabs.resolve_ports();
+
+        // Those already connected stay as they are, with their scans.
+        let mc_connected = |port_name: &str| self.tabs.connd_mtn_ctrlrs.iter().any(|mc| mc.port_name() == port_name);
+        let det_connected = |port_name: &str| self.tabs.connd_detectors.iter().any(|det| det.port_name() == port_name);
+
+        // Calibrations are needed before connecting, as they decide whether to home.
+        let profile = self.tabs.machine_profile();
+        let mcs = profile
+            .motion_controllers
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| !missing.motion_controllers.contains(i) && !mc_connected(&s.port_name))
+            .map(|(_, s)| (DeviceRequest { model: s.model.clone(), port_name: s.port_name.clone() }, s.config.clone()))
+            .collect();
+        let dets = profile
+            .detectors
+            .iter()
+            .enumerate()
+            .filter(|(i, s)| !missing.detectors.contains(i) && !det_connected(&s.port_name))
+            .map(|(_, s)| (DeviceRequest { model: s.model.clone(), port_name: s.port_name.clone() }, s.settings.clone()))
+            .collect();
+
+        if !missing.descriptions.is_empty() {
+            self.dialog(DialogType::Warn, &format!("These instruments are not plugged in and were skipped:\n{}", missing.descriptions.join("\n")));
+        }
+
+        self.connect_profile = profile;
+
+        self.connect_progress = 0.0;
+        self.connect_status.clear();
+        self.connect_errors.clear();
+        self.connecting = Some(connect::start(mcs, dets, ctx.clone()));
+    }
+
+    /// Takes in the devices connected since the last frame.
+    fn poll_connect(&mut self) {
+        let Some(connecting) = &self.connecting else {
+            return;
+        };
+
+        let mut events = Vec::new();
+        let mut finished = false;
+        loop {
+            match connecting.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+
+        for event in events {
+            match event {
+                ConnectEvent::Connecting { done, total, device } => {
+                    self.connect_progress = done as f32 / total.max(1) as f32;
+                    self.connect_status = device;
+                }
+                ConnectEvent::MotionController(mc) => self.tabs.connd_mtn_ctrlrs.push(mc),
+                ConnectEvent::Detector(mut det) => {
+                    // Every detector has as many scans as the others, as the plots and data files pair them up by index.
+                    let kinds: Vec<ScanKind> = self
+                        .tabs
+                        .connd_detectors
+                        .first()
+                        .map_or(Vec::new(), |d| d.get_scans().iter().map(|s| s.kind).collect());
+                    det.start_scans(&kinds);
+                    self.tabs.connd_detectors.push(det);
+                    self.tabs.det_visible.push(true);
+                }
+                ConnectEvent::Failed(e) => self.connect_errors.push(e),
+                ConnectEvent::Finished => finished = true,
+            }
+        }
+
+        if !finished {
+            return;
+        }
+
+        self.connecting = None;
+
+        let profile = std::mem::take(&mut self.connect_profile);
+        self.tabs.assign_from_profile(&profile);
+
+        // Until axes are assigned on the Machine Configuration page, the first controller drives the wavelength.
+        if self.tabs.mai.md_idx.is_none() && !self.tabs.connd_mtn_ctrlrs.is_empty() {
+            self.tabs.mai.md_idx = Some(0);
+        }
+
+        // With two detectors the second defaults to monitoring the lamp.
+        if self.tabs.connd_detectors.len() == 2 && self.tabs.dri.pair().is_none() {
+            self.tabs.dri.sample_idx = Some(0);
+            self.tabs.dri.ref_idx = Some(1);
+        }
+
+        // Detectors connected later read from the next scan on, in which all of them take part.
+        let joined = self.tabs.journaled_detectors > 0 && self.tabs.connd_detectors.len() > self.tabs.journaled_detectors;
+        self.tabs.start_journal();
+        if joined {
+            self.tabs.new_scan();
+        }
+
+        if !self.connect_errors.is_empty() {
+            let message = format!("Some devices could not be connected:\n{}", self.connect_errors.join("\n"));
+            self.dialog(DialogType::Error, &message);
+        }
+    }
+
+    /// Probes the available ports for instruments in the background. See `poll_detect`.
+    fn detect_devices(&mut self, ctx: &egui::Context) {
+        let ports = match serialport::available_ports() {
+            Ok(ports) => ports,
+            Err(e) => {
+                self.dialog(DialogType::Error, &format!("Could not list the serial ports: {}", e));
+                return;
+            }
+        };
+
+        // Connected devices are left alone.
+        let in_use: Vec<String> = self.tabs.connd_mtn_ctrlrs.iter().map(|mc| mc.port_name())
+            .chain(self.tabs.connd_detectors.iter().map(|det| det.port_name()))
+            .collect();
+        let ports: Vec<SerialPortInfo> = ports.into_iter().filter(|p| !in_use.contains(&p.port_name)).collect();
+
+        self.detect_progress = 0.0;
+        self.detect_status.clear();
+        self.detected.clear();
+        self.detecting = Some(connect::detect(ports, ctx.clone()));
+    }
+
+    /// Takes in the instruments found since the last frame, filling in the Device Manager once every port is probed.
+    fn poll_detect(&mut self) {
+        let Some(detecting) = &self.detecting else {
+            return;
+        };
+
+        let mut finished = false;
+        loop {
+            match detecting.try_recv() {
+                Ok(DetectEvent::Probing { done, total, port_name }) => {
+                    self.detect_progress = done as f32 / total.max(1) as f32;
+                    self.detect_status = port_name;
+                }
+                Ok(DetectEvent::Found(found)) => self.detected.push(found),
+                Ok(DetectEvent::Finished) | Err(TryRecvError::Disconnected) => {
+                    finished = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        if !finished {
+            return;
+        }
+
+        self.detecting = None;
+
+        if self.detected.is_empty() {
+            self.dialog(DialogType::Info, "No supported instruments were found.");
+            return;
+        }
+
+        let (mcs, dets): (Vec<&FoundDevice>, Vec<&FoundDevice>) = self.detected.iter().partition(|f| f.motion_controller);
+        let tabs = &mut self.tabs;
+
+        if !mcs.is_empty() {
+            tabs.num_mc_devs = mcs.len().min(10);
+            tabs.sel_mc_port = mcs.iter().take(10).map(|f| f.port_name.clone()).collect();
+            tabs.sel_mc_model = mcs.iter().take(10).map(|f| f.model.to_owned()).collect();
+            tabs.sel_mc_serial = mcs.iter().take(10).map(|f| f.serial_number.clone()).collect();
+            tabs.sel_mc_nick.resize(tabs.num_mc_devs, "None".to_owned());
+        }
+
+        if !dets.is_empty() {
+            tabs.num_det_devs = dets.len().min(2);
+            tabs.sel_det_port = dets.iter().take(2).map(|f| f.port_name.clone()).collect();
+            tabs.sel_det_model = dets.iter().take(2).map(|f| f.model.to_owned()).collect();
+            tabs.sel_det_serial = dets.iter().take(2).map(|f| f.serial_number.clone()).collect();
+            tabs.sel_det_nick.resize(tabs.num_det_devs, "None".to_owned());
+        }
+
+        let found: Vec<String> = self.detected.iter().map(|f| format!("{} on {}", f.model, f.port_name)).collect();
+        self.dialog(DialogType::Info, &format!("Found:\n{}", found.join("\n")));
+    }
+
+    /// Offers to save the scans of a session which did not close cleanly, e.g. because the program crashed.
+    ///
+    /// Until the user decides, its journal is kept where `journal::set_aside` moved it, out of the way of the live one.
+    fn show_recovery(&mut self, ctx: &egui::Context) {
+        let Some(data) = &self.recovered else {
+            return;
+        };
+
+        let mut save = false;
+        let mut discard = false;
+
+        egui::Window::new("Recover Session")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let scans = data.scans.first().map_or(0, |s| s.len());
+                let points: usize = data.scans.first().map_or(0, |s| s.iter().map(|s| s.readings.len()).sum());
+
+                ui.label(format!(
+                    "The last session did not close cleanly. It recorded {} point(s) in {} scan(s) from: {}.",
+                    points,
+                    scans,
+                    data.detectors.join(", ")
+                ));
+
+                ui.horizontal(|ui| {
+                    ui.label("Save As");
+                    ui.text_edit_singleline(&mut self.recovered_path);
+                });
+
+                ui.horizontal(|ui| {
+                    save = ui.button("Save and Open").clicked();
+                    discard = ui.button("Discard").clicked();
+                });
+            });
+
+        if save {
+            let data = self.recovered.as_mut().unwrap();
+            if data.metadata.get("Recovered").is_none() {
+                data.metadata.push("Recovered", datafile::utc_timestamp());
+            }
+
+            let result = match is_archive(&self.recovered_path) {
+                true => datafile::archive::write(&self.recovered_path, data),
+                false => datafile::delimited::write(&self.recovered_path, data, self.tabs.x_unit).map_err(|e| e.to_string()),
+            };
+
+            match result {
+                Ok(_) => {
+                    log::info!("Recovered the last session to {}.", self.recovered_path);
+                    let mut data = self.recovered.take().unwrap();
+                    data.name = self.recovered_path.clone();
+                    self.tabs.imported.push((data, true));
+                    discard = true;
+                }
+                Err(e) => self.dialog(DialogType::Error, &format!("Could not save the recovered session: {}", e)),
+            }
+        }
+
+        if discard {
+            self.recovered = None;
+
+            if let Some(path) = self.recovered_journal.take() {
+                if let Err(e) = std::fs::remove_file(path) {
+                    log::error!("Could not remove the journal of the last session: {}", e);
+                }
+            }
+        }
+    }
+
     /// Should be called each frame a dialog window needs to be shown.
     ///
     /// Should not be used to instantiate an instance of a dialog window, use `dialog()` instead.
@@ -492,48 +2425,135 @@ impl Mcs {
     }
 }
 
-fn gaussian(x: f64) -> f64 {
-    let var: f64 = 2.0;
-    f64::exp(-(x / var).powi(2)) / (var * f64::sqrt(std::f64::consts::TAU))
-}
+/// Shows a grid of the settings particular to a model of device, whichever kinds they are, and returns the one changed.
+///
+/// Numbers are only returned once settled, not on every step of a drag, so that the device is not flooded with them.
+fn settings_grid(ui: &mut egui::Ui, id: &str, settings: &[DriverSetting]) -> Option<(&'static str, SettingValue)> {
+    let mut changed = None;
+
+    egui::Grid::new(id).show(ui, |ui| {
+        for setting in settings {
+            let widget_id = ui.id().with(id).with(setting.name);
+
+            match (setting.kind, &setting.value) {
+                (SettingKind::Number { range, integer, .. }, SettingValue::Number(current)) => {
+                    ui.label(setting.label()).on_hover_text(setting.description);
+
+                    // The driver's value only changes once sent, so a drag in progress is kept aside.
+                    let mut value = ui.data(|d| d.get_temp::<f64>(widget_id)).unwrap_or(*current);
+                    let speed = ((range.1 - range.0) / 500.0).max(if integer { 0.05 } else { 0.0 });
+                    let mut drag = egui::DragValue::new(&mut value).speed(speed).clamp_range(range.0..=range.1);
+                    if integer {
+                        drag = drag.max_decimals(0);
+                    }
+                    let r = ui.add(drag);
+
+                    if r.dragged() {
+                        ui.data_mut(|d| d.insert_temp(widget_id, value));
+                    } else {
+                        ui.data_mut(|d| d.remove::<f64>(widget_id));
+                        if r.changed() || r.drag_stopped() {
+                            let value = if integer { value.round() } else { value };
+                            if value != *current {
+                                changed = Some((setting.name, SettingValue::Number(value)));
+                            }
+                        }
+                    }
+                }
+                (SettingKind::Toggle, SettingValue::Toggle(current)) => {
+                    ui.label(setting.name).on_hover_text(setting.description);
 
-impl McsTabs {
-    fn nested_menus(ui: &mut egui::Ui) {
-        if ui.button("Open...").clicked() {
-            ui.close_menu();
-        }
-        ui.menu_button("SubMenu", |ui| {
-            ui.menu_button("SubMenu", |ui| {
-                if ui.button("Open...").clicked() {
-                    ui.close_menu();
+                    let mut value = *current;
+                    if ui.checkbox(&mut value, "").changed() {
+                        changed = Some((setting.name, SettingValue::Toggle(value)));
+                    }
                 }
-                let _ = ui.button("Item");
-            });
-            ui.menu_button("SubMenu", |ui| {
-                if ui.button("Open...").clicked() {
-                    ui.close_menu();
+                (SettingKind::Choice(choices), SettingValue::Choice(current)) => {
+                    ui.label(setting.name).on_hover_text(setting.description);
+
+                    egui::ComboBox::from_id_source(widget_id)
+                        .selected_text(current.as_str())
+                        .show_ui(ui, |ui| {
+                            for choice in choices {
+                                if ui.selectable_label(choice == current, *choice).clicked() && choice != current {
+                                    changed = Some((setting.name, SettingValue::Choice((*choice).to_owned())));
+                                }
+                            }
+                        });
+                }
+                _ => {
+                    ui.label(setting.name).on_hover_text(setting.description);
+                    ui.label(setting.value.to_string());
                 }
-                let _ = ui.button("Item");
-            });
-            let _ = ui.button("Item");
-            if ui.button("Open...").clicked() {
-                ui.close_menu();
-            }
-        });
-        ui.menu_button("SubMenu", |ui| {
-            let _ = ui.button("Item1");
-            let _ = ui.button("Item2");
-            let _ = ui.button("Item3");
-            let _ = ui.button("Item4");
-            if ui.button("Open...").clicked() {
-                ui.close_menu();
             }
-        });
-        let _ = ui.button("Very long text for this item");
+            ui.end_row();
+        }
+    });
+
+    changed
+}
+
+/// Sends `event` from a worker thread to the GUI, and requests a repaint so that the GUI takes it in.
+///
+/// The GUI dropping its receiver is not an error; the worker simply has nobody left to report to.
+fn send_to_gui<T>(tx: &Sender<T>, ctx: &egui::Context, event: T) {
+    let _ = tx.send(event);
+    ctx.request_repaint();
+}
+
+/// Session archives are told apart from delimited text by their extension.
+fn is_archive(path: &str) -> bool {
+    path.to_lowercase().ends_with(".json")
+}
+
+/// Longest line drawn in a plot. Longer histories are decimated so that plotting stays smooth.
+const MAX_PLOT_POINTS: usize = 2000;
+
+/// Reduces a line to at most about `max_points` points while keeping its peaks and dips.
+///
+/// The points are split into equal buckets, each of which contributes its lowest and highest point in their original order.
+fn decimate(points: &[[f64; 2]], max_points: usize) -> Vec<[f64; 2]> {
+    if points.len() <= max_points {
+        return points.to_vec();
     }
+
+    let bucket = points.len().div_ceil(max_points / 2);
+
+    points
+        .chunks(bucket)
+        .flat_map(|chunk| {
+            let min = chunk.iter().enumerate().min_by(|a, b| a.1[1].total_cmp(&b.1[1])).unwrap();
+            let max = chunk.iter().enumerate().max_by(|a, b| a.1[1].total_cmp(&b.1[1])).unwrap();
+
+            match min.0.cmp(&max.0) {
+                std::cmp::Ordering::Less => vec![*min.1, *max.1],
+                std::cmp::Ordering::Equal => vec![*min.1],
+                std::cmp::Ordering::Greater => vec![*max.1, *min.1],
+            }
+        })
+        .collect()
 }
 
 impl eframe::App for Mcs {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let prefs = Preferences {
+            dark_mode: self.dark_mode,
+            x_unit: self.tabs.x_unit,
+            plot_follow: self.tabs.plot_follow,
+            operator: self.tabs.operator.clone(),
+            data_path: self.tabs.data_path.clone(),
+        };
+
+        eframe::set_value(storage, PROFILE_KEY, &self.tabs.machine_profile());
+        eframe::set_value(storage, PREFERENCES_KEY, &prefs);
+        eframe::set_value(storage, DOCK_KEY, &self.tree);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.tabs.close_journal();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         ctx.set_pixels_per_point(1.5);
 
@@ -573,10 +2593,23 @@ impl eframe::App for Mcs {
 
         // There should only ever be one modal window active, and it should be akin to a dialog window - info, warn, or error.
 
+        self.tabs.poll_scan();
+        self.tabs.poll_motions(ctx);
+        self.poll_connect();
+        self.poll_detect();
+
+        if let Some((dialog_type, message)) = self.tabs.pending_dialog.take() {
+            self.dialog(dialog_type, &message);
+        }
+
         if self.tabs.modal_active {
             self.show_dialog(ctx);
         }
 
+        if self.recovered.is_some() {
+            self.show_recovery(ctx);
+        }
+
         //////////////////////////////////////////////////////////////
         //////////////////////////////////////////////////////////////
         //////////////////////////////////////////////////////////////
@@ -613,8 +2646,28 @@ impl eframe::App for Mcs {
                 ui.horizontal(|ui| {
                     menu::bar(ui, |ui| {
                         ui.menu_button("File", |ui| {
-                            if ui.button("Open").clicked() {
-                                // …
+                            ui.horizontal(|ui| {
+                                ui.label("File");
+                                ui.text_edit_singleline(&mut self.tabs.data_path);
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Operator");
+                                ui.text_edit_singleline(&mut self.tabs.operator);
+                            });
+
+                            if ui.button("Open").on_hover_text("Open saved scans and overlay them on the plot.").clicked() {
+                                self.tabs.import_scans();
+                                ui.close_menu();
+                            }
+
+                            let has_scans = !self.tabs.connd_detectors.is_empty();
+                            if ui
+                                .add_enabled(has_scans, egui::Button::new("Export Scans"))
+                                .on_hover_text("Save all scans as CSV, as TSV if the file ends in .tsv, or as a session archive if it ends in .json.")
+                                .clicked()
+                            {
+                                self.tabs.export_scans();
+                                ui.close_menu();
                             }
                         });
                         ui.menu_button("Edit", |ui| {
@@ -752,8 +2805,26 @@ impl eframe::App for Mcs {
 
                     ///////////
 
-                    if ui.button("Search for Devices").clicked() {
-                        self.search_ports = true;
+                    ui.horizontal(|ui| {
+                        if ui.button("Search for Devices").clicked() {
+                            self.search_ports = true;
+                        }
+
+                        if ui
+                            .add_enabled(self.detecting.is_none() && self.connecting.is_none(), egui::Button::new("Auto-detect"))
+                            .on_hover_text("Probe every port for supported instruments and fill in the devices below.")
+                            .clicked()
+                        {
+                            self.detect_devices(ctx);
+                        }
+                    });
+
+                    if self.detecting.is_some() {
+                        ui.add(
+                            egui::ProgressBar::new(self.detect_progress)
+                                .animate(true)
+                                .text(format!("Probing {}...", self.detect_status)),
+                        );
                     }
 
                     ui.label("Motion Controllers");
@@ -769,6 +2840,10 @@ impl eframe::App for Mcs {
                             self.tabs.sel_mc_port.push("None".to_owned());
                         }
 
+                        if self.tabs.sel_mc_serial.len() < i + 1 {
+                            self.tabs.sel_mc_serial.push(None);
+                        }
+
                         if self.tabs.sel_mc_model.len() < i + 1 {
                             self.tabs.sel_mc_model.push("None".to_owned());
                         }
@@ -792,7 +2867,7 @@ impl eframe::App for Mcs {
                                 // Generate combo-box items in a loop.
                                 if let Ok(ports) = self.ports.as_ref() {
                                     for device in ports.iter() {
-                                        ui.selectable_value(
+                                        if ui.selectable_value(
                                             &mut self.tabs.sel_mc_port[i],
                                             device.port_name.clone(),
                                             format!(
@@ -801,7 +2876,9 @@ impl eframe::App for Mcs {
                                                 device.port_type,
                                                 device.type_id()
                                             ),
-                                        );
+                                        ).clicked() {
+                                            self.tabs.sel_mc_serial[i] = profile::usb_serial_number(device);
+                                        }
                                     }
                                 }
                             });
@@ -816,11 +2893,11 @@ impl eframe::App for Mcs {
                                 ui.set_min_width(60.0);
                                 
                                 // Generate combo-box items in a loop.
-                                for model in self.mtn_ctrl_models.iter() {
+                                for model in drivers::registry::MOTION_CONTROL_MODELS.iter() {
                                     ui.selectable_value(
                                         &mut self.tabs.sel_mc_model[i],
-                                        model.to_string(),
-                                        model,
+                                        model.name.to_string(),
+                                        model.name,
                                     );
                                 }
                             });
@@ -840,6 +2917,10 @@ impl eframe::App for Mcs {
                             self.tabs.sel_det_port.push("None".to_owned());
                         }
 
+                        if self.tabs.sel_det_serial.len() < i + 1 {
+                            self.tabs.sel_det_serial.push(None);
+                        }
+
                         if self.tabs.sel_det_model.len() < i + 1 {
                             self.tabs.sel_det_model.push("None".to_owned());
                         }
@@ -859,7 +2940,7 @@ impl eframe::App for Mcs {
                                     // Generate combo-box items in a loop.
                                     if let Ok(ports) = self.ports.as_ref() {
                                         for device in ports.iter() {
-                                            ui.selectable_value(
+                                            if ui.selectable_value(
                                                 &mut self.tabs.sel_det_port[i],
                                                 device.port_name.clone(),
                                                 format!(
@@ -868,7 +2949,9 @@ impl eframe::App for Mcs {
                                                     device.port_type,
                                                     device.type_id()
                                                 ),
-                                            );
+                                            ).clicked() {
+                                                self.tabs.sel_det_serial[i] = profile::usb_serial_number(device);
+                                            }
                                         }
                                     }
                                 });
@@ -881,11 +2964,11 @@ impl eframe::App for Mcs {
                                     ui.set_min_width(60.0);
 
                                     // Generate combo-box items in a loop.
-                                    for model in self.det_models.iter() {
+                                    for model in drivers::registry::DETECTOR_MODELS.iter() {
                                         ui.selectable_value(
                                             &mut self.tabs.sel_det_model[i],
-                                            model.to_string(),
-                                            model,
+                                            model.name.to_string(),
+                                            model.name,
                                         );
                                     }
                                 });
@@ -900,40 +2983,19 @@ impl eframe::App for Mcs {
                     ui.add_space(15.0);
 
                     if ui
-                        .button("Connect Devices")
+                        .add_enabled(self.connecting.is_none() && self.tabs.scan.is_none(), egui::Button::new("Connect Devices"))
                         .on_hover_text("Search for and connect devices as selected above.")
                         .clicked()
                     {
-                        // TODO: Obviously this closure is mostly test code- requires extensive overhaul!!!
-                        self.devices_loading = true;
-                        self.devices_loading_progress = 0.0;
-
-                        // Set up the devices vectors.
-                        for i in 0..self.tabs.num_mc_devs {
-                            let mc = MotionController::new(Box::new(drivers::mp_789a_4::Mp789a4Virtual::new(self.tabs.sel_mc_port[i].clone()).unwrap()));
-                            self.connd_mtn_ctrlrs.push(mc);
-                        }
-
-                        for i in 0..self.tabs.num_det_devs {
-                            let det = Detector::new(Box::new(drivers::ki_6485::Ki6485::new(self.tabs.sel_det_port[i].clone(), 10).unwrap()));
-                            self.tabs.connd_detectors.push(det);
-
-                            // Make a new vec for each detector.
-                            self.tabs.detector_data.push(Vec::new());
-                        }
+                        self.connect_devices(ctx);
                     }
 
-                    if self.devices_loading {
+                    if self.connecting.is_some() {
                         ui.add(
-                            egui::ProgressBar::new(self.devices_loading_progress)
-                                .animate(self.devices_loading)
-                                .show_percentage(),
+                            egui::ProgressBar::new(self.connect_progress)
+                                .animate(true)
+                                .text(format!("Connecting {}...", self.connect_status)),
                         );
-                        self.devices_loading_progress += 0.01 / 60.0;
-
-                        if self.devices_loading_progress >= 1.0 {
-                            self.devices_loading = false;
-                        }
                     }
 
                     // Since the GUI is immediate mode, we need to lock the ports search behind a boolean to prevent overrequesting.
@@ -970,7 +3032,7 @@ impl eframe::App for Mcs {
                 }
 
                 ActivePage::MachineConfig => {
-                    ui.label("Machine Configuration");
+                    self.tabs.machine_config(ui);
                 }
             }
         });
@@ -1,58 +1,544 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
 use crate::drivers;
 
+// Drivers are shared between the GUI and the threads which run scans and movements.
+pub type SharedMotionControlDriver = Arc<Mutex<Box<dyn drivers::MotionControlDriver>>>;
+pub type SharedDetectorDriver = Arc<Mutex<Box<dyn drivers::DetectorDriver>>>;
+pub type SharedMotionStatus = Arc<Mutex<MotionStatus>>;
+
+pub const MONITOR_INTERVAL: Duration = Duration::from_millis(500); // How often idle motion controllers are polled.
+
+// The axes of movement of the spectrometer, each of which may be driven by one of the connected motion controllers.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Axis {
+    MainDrive,
+    FilterWheel,
+    SampleRotation,
+    SampleAngle,
+    SampleTranslation,
+    DetectorRotation,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 6] = [
+        Axis::MainDrive,
+        Axis::FilterWheel,
+        Axis::SampleRotation,
+        Axis::SampleAngle,
+        Axis::SampleTranslation,
+        Axis::DetectorRotation,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Axis::MainDrive => "Main Drive",
+            Axis::FilterWheel => "Filter Wheel",
+            Axis::SampleRotation => "Sample Rotation",
+            Axis::SampleAngle => "Sample Angle",
+            Axis::SampleTranslation => "Sample Translation",
+            Axis::DetectorRotation => "Detector Rotation",
+        }
+    }
+
+    /// The physical unit positions on this axis are given in.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Axis::MainDrive => "nm",
+            Axis::FilterWheel => "slot",
+            Axis::SampleRotation | Axis::SampleAngle | Axis::DetectorRotation => "°",
+            Axis::SampleTranslation => "mm",
+        }
+    }
+}
+
 // Holds an index corresponding to each axis of movement.
-// The index is set by the user when they assign a device to an axis using a combobox.
+// The index is set by the user when they assign a device to an axis using a combobox on the Machine Configuration page.
 // This allows the GUI to then access the arbitrarily ordered list of MotionControllers using these indices.
+#[derive(Default)]
 pub struct MovementAxesIndices {
     pub md_idx: Option<usize>, // main drive
-    pub fw_idx: Option<MotionController>, // filter wheel
+    pub fw_idx: Option<usize>, // filter wheel
     pub sr_idx: Option<usize>, // sample rotation
     pub sa_idx: Option<usize>, // sample angle
     pub st_idx: Option<usize>, // sample translation
     pub dr_idx: Option<usize>, // detector rotation
 }
 
-impl Default for MovementAxesIndices {
-    fn default() -> MovementAxesIndices {
-        MovementAxesIndices {
-            md_idx: None,
-            fw_idx: None,
-            sr_idx: None,
-            sa_idx: None,
-            st_idx: None,
-            dr_idx: None,
+impl MovementAxesIndices {
+    pub fn get(&self, axis: Axis) -> Option<usize> {
+        match axis {
+            Axis::MainDrive => self.md_idx,
+            Axis::FilterWheel => self.fw_idx,
+            Axis::SampleRotation => self.sr_idx,
+            Axis::SampleAngle => self.sa_idx,
+            Axis::SampleTranslation => self.st_idx,
+            Axis::DetectorRotation => self.dr_idx,
         }
     }
+
+    pub fn get_mut(&mut self, axis: Axis) -> &mut Option<usize> {
+        match axis {
+            Axis::MainDrive => &mut self.md_idx,
+            Axis::FilterWheel => &mut self.fw_idx,
+            Axis::SampleRotation => &mut self.sr_idx,
+            Axis::SampleAngle => &mut self.sa_idx,
+            Axis::SampleTranslation => &mut self.st_idx,
+            Axis::DetectorRotation => &mut self.dr_idx,
+        }
+    }
+
+    /// Returns the axis the controller at `mc_idx` is assigned to, if any.
+    pub fn axis_of(&self, mc_idx: usize) -> Option<Axis> {
+        Axis::ALL.into_iter().find(|&a| self.get(a) == Some(mc_idx))
+    }
+}
+
+// Holds the index of the detector assigned to each measurement role.
+// The sample detector sees the beam after the sample, the reference detector monitors the lamp through a beam splitter.
+// Dividing the former by the latter normalizes out lamp intensity fluctuations.
+#[derive(Default)]
+pub struct DetectorRoleIndices {
+    pub sample_idx: Option<usize>,
+    pub ref_idx: Option<usize>,
+}
+
+impl DetectorRoleIndices {
+    /// Returns the (sample, reference) index pair if both roles are assigned to different detectors.
+    pub fn pair(&self) -> Option<(usize, usize)> {
+        match (self.sample_idx, self.ref_idx) {
+            (Some(s), Some(r)) if s != r => Some((s, r)),
+            _ => None,
+        }
+    }
+}
+
+/// Computes the point-by-point ratio of a sample trace to its reference trace.
+///
+/// Points where the reference reads zero have no meaningful ratio and are returned as NaN.
+pub fn normalize(sample: &[f64], reference: &[f64]) -> Vec<f64> {
+    sample
+        .iter()
+        .zip(reference.iter())
+        .map(|(&s, &r)| if r == 0.0 { f64::NAN } else { s / r })
+        .collect()
+}
+
+// When a motion controller homes on its own, besides whenever the user asks it to.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum HomeBehavior {
+    #[default]
+    OnConnect,
+    BeforeScan, // On connecting and again before every scan, to rule out lost steps.
+    Manual,     // Positions are meaningless until the user homes.
+}
+
+impl HomeBehavior {
+    pub const ALL: [HomeBehavior; 3] = [HomeBehavior::OnConnect, HomeBehavior::BeforeScan, HomeBehavior::Manual];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            HomeBehavior::OnConnect => "On Connect",
+            HomeBehavior::BeforeScan => "Before Every Scan",
+            HomeBehavior::Manual => "Manual",
+        }
+    }
+}
+
+// How backlash in the drive train is taken out of movements.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum BacklashMode {
+    #[default]
+    Approach, // Every move ends in the approach direction, overshooting the target by the backlash when coming the other way.
+    Reversal, // Moves which reverse direction go the backlash further, to take up the slack. No overshoot.
+}
+
+impl BacklashMode {
+    pub const ALL: [BacklashMode; 2] = [BacklashMode::Approach, BacklashMode::Reversal];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            BacklashMode::Approach => "Approach From One Side",
+            BacklashMode::Reversal => "Compensate On Reversal",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Approach {
+    #[default]
+    FromBelow,
+    FromAbove,
+}
+
+impl Approach {
+    pub const ALL: [Approach; 2] = [Approach::FromBelow, Approach::FromAbove];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Approach::FromBelow => "From Below",
+            Approach::FromAbove => "From Above",
+        }
+    }
+}
+
+// Calibration of a motion controller, turning driver steps into physical values on the axis it drives.
+// All values but `steps_per_value` are in the physical unit of the axis.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisConfig {
+    pub steps_per_value: f64, // Driver steps per physical unit (nm, deg, ...).
+    pub offset: f64,          // Physical value at the home position, where the driver reads 0 steps.
+    pub limits: Option<(f64, f64)>, // Lowest and highest value the axis may be moved to.
+    pub tolerance: f64, // How far the controller's count may stray from where it was sent before steps count as lost.
+    pub backlash: f64,
+    pub backlash_mode: BacklashMode,
+    pub approach: Approach, // Only for `BacklashMode::Approach`.
+    pub home: HomeBehavior,
+    pub driver_settings: Vec<(String, drivers::SettingValue)>, // Of the model of controller, by name, as changed from their defaults.
+    pub jog_fine: f64,   // Distance of the small jog buttons.
+    pub jog_coarse: f64, // Distance of the large jog buttons.
+    pub jog_speed: f64,  // Per second, when holding a jog button down.
+}
+
+impl Default for AxisConfig {
+    fn default() -> Self {
+        AxisConfig {
+            steps_per_value: 1.0,
+            offset: 0.0,
+            limits: None,
+            tolerance: 0.0,
+            backlash: 0.0,
+            backlash_mode: BacklashMode::default(),
+            approach: Approach::default(),
+            home: HomeBehavior::default(),
+            driver_settings: Vec::new(),
+            jog_fine: 1.0,
+            jog_coarse: 10.0,
+            jog_speed: 5.0,
+        }
+    }
+}
+
+impl AxisConfig {
+    pub fn value_to_steps(&self, value: f64) -> i64 {
+        ((value - self.offset) * self.steps_per_value).round() as i64
+    }
+
+    pub fn steps_to_value(&self, steps: i64) -> f64 {
+        steps as f64 / self.steps_per_value + self.offset
+    }
+
+    /// Returns the jog velocity in steps per second, in the direction of the sign of `direction`.
+    pub fn jog_velocity(&self, direction: f64) -> i64 {
+        (self.jog_speed * self.steps_per_value).abs().round() as i64 * direction.signum() as i64
+    }
+
+    pub fn tolerance_steps(&self) -> i64 {
+        (self.tolerance * self.steps_per_value).abs().round() as i64
+    }
+
+    pub fn backlash_steps(&self) -> i64 {
+        (self.backlash * self.steps_per_value).abs().round() as i64
+    }
+
+    /// Returns an error naming the limit if `value` lies outside of them.
+    pub fn check_limits(&self, value: f64) -> Result<(), String> {
+        match self.limits {
+            Some((min, _)) if value < min => Err(format!("{} is below the lower limit of {}.", value, min)),
+            Some((_, max)) if value > max => Err(format!("{} is above the upper limit of {}.", value, max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum MotionState {
+    #[default]
+    Idle,
+    Moving,
+    Homing,
+}
+
+impl MotionState {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MotionState::Idle => "Idle",
+            MotionState::Moving => "Moving",
+            MotionState::Homing => "Homing",
+        }
+    }
+}
+
+// What is known of a motion controller, kept up to date by whichever thread is using it, so the GUI never waits on a
+// busy driver. While nobody is, a monitor thread polls the driver instead.
+#[derive(Clone, Default)]
+pub struct MotionStatus {
+    pub state: MotionState,
+    pub position: Option<i64>, // Steps, as last read from the controller, or else the driver.
+    pub homed: bool,
+    pub position_lost: bool, // Steps were lost. Positions are meaningless until homed again.
+    pub last_error: Option<String>,
+    pub switches: Option<drivers::LimitStatus>, // As last read from the controller.
+    pub slack: i64, // Steps the driver is ahead of the axis, from taking up backlash on reversal. 0 after homing.
+}
+
+/// Runs `f` on the driver, showing `state` in the status meanwhile, and records the position and any error after.
+///
+/// Every movement of a motion controller should go through here, whichever thread it is made from.
+pub fn run_motion<T>(
+    driver: &SharedMotionControlDriver,
+    status: &SharedMotionStatus,
+    state: MotionState,
+    f: impl FnOnce(&mut dyn drivers::MotionControlDriver) -> Result<T, serialport::Error>,
+) -> Result<T, serialport::Error> {
+    status.lock().unwrap().state = state;
+
+    let mut drv = driver.lock().unwrap();
+    let result = f(drv.as_mut());
+    let position = read_steps(drv.as_mut());
+    drop(drv);
+
+    let mut status = status.lock().unwrap();
+    status.state = MotionState::Idle;
+    if state == MotionState::Homing && result.is_ok() {
+        status.slack = 0;
+        status.position_lost = false;
+    }
+    status.position = Some(position - status.slack);
+    match &result {
+        Ok(_) if state == MotionState::Homing => status.homed = true,
+        Ok(_) => {}
+        Err(e) => status.last_error = Some(e.to_string()),
+    }
+
+    result
+}
+
+/// Moves the axis to `value`, in its physical unit, taking out backlash as `config` says.
+///
+/// Every move to a position should go through here, so that manual moves and scans approach targets alike. Limits are
+/// left to the caller.
+pub fn move_axis(
+    driver: &SharedMotionControlDriver,
+    status: &SharedMotionStatus,
+    config: &AxisConfig,
+    value: f64,
+) -> Result<(), serialport::Error> {
+    if status.lock().unwrap().position_lost {
+        return Err(serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            "Steps were lost. Home the axis before moving it again.",
+        ));
+    }
+
+    run_motion(driver, status, MotionState::Moving, |drv| {
+        move_steps(drv, status, config, value)?;
+        check_switches(drv, status)?;
+        verify(drv, status, config)
+    })
+}
+
+fn move_steps(
+    drv: &mut dyn drivers::MotionControlDriver,
+    status: &SharedMotionStatus,
+    config: &AxisConfig,
+    value: f64,
+) -> Result<(), serialport::Error> {
+    let slack = status.lock().unwrap().slack;
+    let backlash = config.backlash_steps();
+    let from = drv.get_position() - slack;
+    let to = config.value_to_steps(value);
+
+    match config.backlash_mode {
+        BacklashMode::Approach => {
+            let overshoot = match config.approach {
+                Approach::FromBelow if to < from => -backlash,
+                Approach::FromAbove if to > from => backlash,
+                _ => 0,
+            };
+            if overshoot != 0 {
+                drv.move_to(to + slack + overshoot, 0)?;
+            }
+            drv.move_to(to + slack, 0)
+        }
+        BacklashMode::Reversal => {
+            // The driver leads the axis by the backlash after moving up, and not after moving down.
+            let new_slack = match to.cmp(&from) {
+                std::cmp::Ordering::Greater => backlash,
+                std::cmp::Ordering::Less => 0,
+                std::cmp::Ordering::Equal => slack,
+            };
+            drv.move_to(to + new_slack, 0)?;
+            status.lock().unwrap().slack = new_slack;
+            Ok(())
+        }
+    }
+}
+
+/// Fails if the drive is on a limit switch, as after running into one, making sure it is stopped.
+///
+/// The switch stops the drive short of where it was sent, so the position is lost too.
+pub fn check_switches(drv: &mut dyn drivers::MotionControlDriver, status: &SharedMotionStatus) -> Result<(), serialport::Error> {
+    let switches = drv.limit_status()?;
+    status.lock().unwrap().switches = Some(switches);
+
+    if !switches.at_limit() {
+        return Ok(());
+    }
+
+    drv.stop()
+        .map_err(|e| serialport::Error::new(serialport::ErrorKind::Unknown, e.to_string()))?;
+
+    let mut status = status.lock().unwrap();
+    status.position_lost = true;
+    status.homed = false;
+
+    let msg = format!(
+        "Ran into the {} limit switch and stopped. Home the axis before moving it again.",
+        if switches.upper { "upper" } else { "lower" }
+    );
+    log::error!("{}", msg);
+    Err(serialport::Error::new(serialport::ErrorKind::Unknown, msg))
+}
+
+/// Compares the controller's count with where the driver sent the motor, if the controller keeps one.
+///
+/// A difference beyond the tolerance means steps were lost, e.g. to a stall or an interrupted move, so the position
+/// can no longer be trusted. The axis is then refused further moves until it is homed.
+fn verify(
+    drv: &mut dyn drivers::MotionControlDriver,
+    status: &SharedMotionStatus,
+    config: &AxisConfig,
+) -> Result<(), serialport::Error> {
+    let commanded = drv.get_position();
+    let Some(actual) = drv.read_position()? else {
+        return Ok(());
+    };
+
+    if (actual - commanded).abs() <= config.tolerance_steps() {
+        return Ok(());
+    }
+
+    let mut status = status.lock().unwrap();
+    status.position_lost = true;
+    status.homed = false;
+
+    let msg = format!(
+        "Steps were lost: sent to {} steps, but the controller counts {}. Home the axis before moving it again.",
+        commanded, actual
+    );
+    log::error!("{}", msg);
+    Err(serialport::Error::new(serialport::ErrorKind::Unknown, msg))
+}
+
+/// Returns the controller's count, falling back on the driver's when the controller has none or does not answer.
+fn read_steps(drv: &mut dyn drivers::MotionControlDriver) -> i64 {
+    match drv.read_position() {
+        Ok(Some(position)) => position,
+        _ => drv.get_position(),
+    }
+}
+
+/// Returns the position of the axis in steps, as the driver reads it less any slack taken up.
+pub fn axis_steps(driver: &SharedMotionControlDriver, status: &SharedMotionStatus) -> i64 {
+    let slack = status.lock().unwrap().slack;
+    read_steps(driver.lock().unwrap().as_mut()) - slack
+}
+
+/// Polls the position of an idle motion controller until it is dropped.
+fn monitor(driver: Weak<Mutex<Box<dyn drivers::MotionControlDriver>>>, status: Weak<Mutex<MotionStatus>>) {
+    thread::spawn(move || loop {
+        let (Some(driver), Some(status)) = (driver.upgrade(), status.upgrade()) else {
+            return;
+        };
+
+        // A busy driver is being used by a worker, which updates the status itself.
+        let read = driver
+            .try_lock()
+            .ok()
+            .map(|mut drv| (read_steps(drv.as_mut()), drv.limit_status().ok()));
+        if let Some((position, switches)) = read {
+            let mut status = status.lock().unwrap();
+            status.position = Some(position - status.slack);
+            status.switches = switches;
+        }
+
+        drop(driver);
+        drop(status);
+        thread::sleep(MONITOR_INTERVAL);
+    });
 }
 
 pub trait MotionControlMiddleware {
     fn all_stop(&self);
-    fn set_limits(&self);
-    fn set_offset(&self);
-    fn get_offset(&self);
-    fn set_steps_per_value(&self);
-    fn get_steps_per_value(&self);
+    fn set_limits(&mut self, limits: Option<(f64, f64)>);
+    fn get_limits(&self) -> Option<(f64, f64)>;
+    fn set_offset(&mut self, offset: f64);
+    fn get_offset(&self) -> f64;
+    fn set_steps_per_value(&mut self, steps_per_value: f64);
+    fn get_steps_per_value(&self) -> f64;
+    fn config(&self) -> &AxisConfig;
+    fn config_mut(&mut self) -> &mut AxisConfig;
     // fn is_dummy(&self);
-    fn home(&self);
-    fn get_position(&self);
+    fn home(&mut self) -> Result<(), serialport::Error>;
+    fn get_position(&mut self) -> f64;
+    fn status(&self) -> MotionStatus;
+    fn shared_status(&self) -> SharedMotionStatus;
+    fn capabilities(&self) -> drivers::MotionCapabilities;
+    fn set_user_zero(&mut self, zero: Option<f64>);
+    fn settings(&self) -> &[drivers::DriverSetting];
+    fn set_setting(&mut self, name: &str, value: drivers::SettingValue) -> Result<(), serialport::Error>;
+    fn restore_settings(&mut self) -> Result<(), serialport::Error>;
+    fn user_zero(&self) -> Option<f64>;
     fn is_homing(&self);
     fn is_moving(&self);
     fn move_to(&self);
     fn stop(&self);
-    fn port_name(&self);
-    fn short_name(&self);
-    fn long_name(&self);
+    fn port_name(&self) -> String;
+    fn short_name(&self) -> String;
+    fn long_name(&self) -> String;
+    fn identification(&self) -> String;
 }
 
 pub struct MotionController {
-    pub driver: Box<dyn drivers::MotionControlDriver>,
+    pub driver: SharedMotionControlDriver,
+    status: SharedMotionStatus,
+
+    // Cached so that the GUI never waits on a driver busy with a movement.
+    port_name: String,
+    short_name: String,
+    long_name: String,
+    identification: String,
+    capabilities: drivers::MotionCapabilities,
+    settings: Vec<drivers::DriverSetting>,
+
+    config: AxisConfig,
+    user_zero: Option<f64>, // The position shown as 0 until cleared, in physical units. Not saved with the profile.
 }
 
 impl MotionController {
-    pub fn new(driver: Box<dyn drivers::MotionControlDriver>) -> MotionController {
-        MotionController {
-            driver,
-        }
+    pub fn new(mut driver: Box<dyn drivers::MotionControlDriver>, port_name: String) -> MotionController {
+        let mc = MotionController {
+            port_name,
+            short_name: driver.short_name(),
+            long_name: driver.long_name(),
+            identification: driver.identification(),
+            capabilities: driver.capabilities(),
+            settings: driver.settings(),
+            driver: Arc::new(Mutex::new(driver)),
+            status: SharedMotionStatus::default(),
+            config: AxisConfig::default(),
+            user_zero: None,
+        };
+
+        monitor(Arc::downgrade(&mc.driver), Arc::downgrade(&mc.status));
+
+        mc
     }
 }
 
@@ -61,32 +547,92 @@ impl MotionControlMiddleware for MotionController {
         todo!()
     }
 
-    fn set_limits(&self) {
-        todo!()
+    fn set_limits(&mut self, limits: Option<(f64, f64)>) {
+        self.config.limits = limits;
     }
 
-    fn set_offset(&self) {
-        todo!()
+    fn get_limits(&self) -> Option<(f64, f64)> {
+        self.config.limits
     }
 
-    fn get_offset(&self) {
-        todo!()
+    fn set_offset(&mut self, offset: f64) {
+        self.config.offset = offset;
     }
 
-    fn set_steps_per_value(&self) {
-        todo!()
+    fn get_offset(&self) -> f64 {
+        self.config.offset
     }
 
-    fn get_steps_per_value(&self) {
-        todo!()
+    fn set_steps_per_value(&mut self, steps_per_value: f64) {
+        self.config.steps_per_value = steps_per_value;
     }
 
-    fn home(&self) {
-        todo!()
+    fn get_steps_per_value(&self) -> f64 {
+        self.config.steps_per_value
     }
 
-    fn get_position(&self) {
-        todo!()
+    fn config(&self) -> &AxisConfig {
+        &self.config
+    }
+
+    fn config_mut(&mut self) -> &mut AxisConfig {
+        &mut self.config
+    }
+
+    /// Blocks until the controller has homed.
+    fn home(&mut self) -> Result<(), serialport::Error> {
+        run_motion(&self.driver, &self.status, MotionState::Homing, |drv| drv.home())
+    }
+
+    /// Returns the position in physical units.
+    fn get_position(&mut self) -> f64 {
+        self.config.steps_to_value(axis_steps(&self.driver, &self.status))
+    }
+
+    fn status(&self) -> MotionStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    fn shared_status(&self) -> SharedMotionStatus {
+        self.status.clone()
+    }
+
+    fn capabilities(&self) -> drivers::MotionCapabilities {
+        self.capabilities
+    }
+
+    fn set_user_zero(&mut self, zero: Option<f64>) {
+        self.user_zero = zero;
+    }
+
+    fn user_zero(&self) -> Option<f64> {
+        self.user_zero
+    }
+
+    fn settings(&self) -> &[drivers::DriverSetting] {
+        &self.settings
+    }
+
+    /// Changes a setting of the driver, and keeps it in the config so that it is saved with the profile.
+    fn set_setting(&mut self, name: &str, value: drivers::SettingValue) -> Result<(), serialport::Error> {
+        let mut drv = self.driver.lock().unwrap();
+        drv.set_setting(name, value.clone())?;
+        self.settings = drv.settings();
+        drop(drv);
+
+        keep_setting(&mut self.config.driver_settings, name, value);
+        Ok(())
+    }
+
+    /// Gives the driver the settings kept in the config, as after connecting.
+    fn restore_settings(&mut self) -> Result<(), serialport::Error> {
+        let mut drv = self.driver.lock().unwrap();
+        for (name, value) in self.config.driver_settings.iter() {
+            drv.set_setting(name, value.clone())?;
+        }
+        self.settings = drv.settings();
+
+        Ok(())
     }
 
     fn is_homing(&self) {
@@ -105,39 +651,141 @@ impl MotionControlMiddleware for MotionController {
         todo!()
     }
 
-    fn port_name(&self) {
-        todo!()
+    fn port_name(&self) -> String {
+        self.port_name.clone()
     }
 
-    fn short_name(&self) {
-        todo!()
+    fn short_name(&self) -> String {
+        self.short_name.clone()
     }
 
-    fn long_name(&self) {
-        todo!()
+    fn long_name(&self) -> String {
+        self.long_name.clone()
+    }
+
+    fn identification(&self) -> String {
+        self.identification.clone()
+    }
+}
+
+/// Keeps a changed driver setting among those saved with the profile.
+fn keep_setting(saved: &mut Vec<(String, drivers::SettingValue)>, name: &str, value: drivers::SettingValue) {
+    match saved.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => *v = value,
+        None => saved.push((name.to_owned(), value)),
+    }
+}
+
+// What a scan measured, as marked by the user. Needed to derive reflectance or transmittance.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ScanKind {
+    #[default]
+    Unmarked,
+    Dark,     // Beam blocked.
+    Baseline, // No sample in the beam (I0).
+    Sample,   // Sample in the beam (I).
+}
+
+impl ScanKind {
+    pub const ALL: [ScanKind; 4] = [ScanKind::Unmarked, ScanKind::Dark, ScanKind::Baseline, ScanKind::Sample];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ScanKind::Unmarked => "Unmarked",
+            ScanKind::Dark => "Dark",
+            ScanKind::Baseline => "Baseline",
+            ScanKind::Sample => "Sample",
+        }
     }
 }
 
+#[derive(Clone, Default)]
+pub struct Scan {
+    pub kind: ScanKind,
+    pub positions: Vec<f64>, // Main drive position of each reading.
+    pub readings: Vec<f64>,
+    pub timestamps: Vec<f64>, // Seconds since the Unix epoch. May be empty for scans loaded from files without them.
+    pub settings: Vec<(String, String)>, // Of the detector as the scan began, by label, e.g. ("Range", "Auto").
+}
+
+/// Returns the current time in seconds since the Unix epoch, as stored with each reading.
+pub fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
 pub trait DetectorMiddleware {
     fn new_scan(&mut self);
-    fn get_last_scan(&self) -> Vec<f64>;
-    fn detect(&mut self) -> f64;
-    fn short_name(&mut self);
-    fn long_name(&mut self);
+    fn start_scans(&mut self, kinds: &[ScanKind]);
+    fn get_last_scan(&self) -> Scan;
+    fn get_scans(&self) -> &Vec<Scan>;
+    fn set_scan_kind(&mut self, scan_idx: usize, kind: ScanKind);
+    fn detect(&mut self, position: f64) -> Result<f64, serialport::Error>;
+    fn record(&mut self, position: f64, reading: f64, timestamp: f64);
+    fn latest(&self) -> Option<f64>;
+    fn is_overflow(&self) -> bool;
+    fn range(&self) -> String;
+    fn capabilities(&self) -> drivers::DetectorCapabilities;
+    fn settings(&self) -> &[drivers::DriverSetting];
+    fn set_setting(&mut self, name: &str, value: drivers::SettingValue) -> Result<(), serialport::Error>;
+    fn restore_settings(&mut self, saved: Vec<(String, drivers::SettingValue)>) -> Result<(), serialport::Error>;
+    fn saved_settings(&self) -> &[(String, drivers::SettingValue)];
+    fn acquire_zero(&mut self) -> Result<(), serialport::Error>;
+    fn set_error(&mut self, error: String);
+    fn last_error(&self) -> Option<String>;
+    fn port_name(&self) -> String;
+    fn short_name(&self) -> String;
+    fn long_name(&self) -> String;
+    fn identification(&self) -> String;
 }
 
 pub struct Detector {
-    pub driver: Box<dyn drivers::DetectorDriver>,
-    
-    scans: Vec<Vec<f64>>,
+    pub driver: SharedDetectorDriver,
+
+    // Cached, as for `MotionController`.
+    port_name: String,
+    short_name: String,
+    long_name: String,
+    identification: String,
+    range: String,
+    capabilities: drivers::DetectorCapabilities,
+    settings: Vec<drivers::DriverSetting>,
+
+    saved_settings: Vec<(String, drivers::SettingValue)>, // As changed from their defaults, to save with the profile.
+    scans: Vec<Scan>,
+
+    // Status, as last seen by whichever thread read the detector.
+    latest: Option<f64>,
+    last_error: Option<String>,
 }
 
 impl Detector {
-    pub fn new(driver: Box<dyn drivers::DetectorDriver>) -> Detector {
+    pub fn new(mut driver: Box<dyn drivers::DetectorDriver>, port_name: String) -> Detector {
         Detector {
-            driver,
+            port_name,
+            short_name: driver.short_name(),
+            long_name: driver.long_name(),
+            identification: driver.identification(),
+            range: driver.range(),
+            capabilities: driver.capabilities(),
+            settings: driver.settings(),
+            driver: Arc::new(Mutex::new(driver)),
+            saved_settings: Vec::new(),
             scans: Vec::new(),
+            latest: None,
+            last_error: None,
+        }
+    }
+
+    /// Describes the settings in effect, to keep with a scan.
+    fn settings_snapshot(&self) -> Vec<(String, String)> {
+        let mut snapshot = Vec::new();
+        if self.capabilities.ranging {
+            snapshot.push(("Range".to_owned(), self.range.clone()));
         }
+        snapshot.extend(self.settings.iter().map(|s| (s.label(), s.value.to_string())));
+        snapshot
     }
 }
 
@@ -145,27 +793,142 @@ impl DetectorMiddleware for Detector {
     /// Detector data is always appended to the latest vector in scans.
     /// This function creates a new empty scan vector for following data.
     fn new_scan(&mut self) {
-        self.scans.push(Vec::new());
+        self.scans.push(Scan {
+            settings: self.settings_snapshot(),
+            ..Scan::default()
+        });
     }
 
-    fn get_last_scan(&self) -> Vec<f64> {
+    /// Starts the scans of a newly connected detector, with an empty one for each of `kinds`, the scans the other
+    /// detectors of the session already have. With none, it starts the first scan.
+    fn start_scans(&mut self, kinds: &[ScanKind]) {
+        if kinds.is_empty() {
+            return self.new_scan();
+        }
+
+        // It was not connected for those, so there are no settings to record with them.
+        self.scans = kinds.iter().map(|&kind| Scan { kind, ..Scan::default() }).collect();
+    }
+
+    fn get_last_scan(&self) -> Scan {
         self.scans.last().unwrap().to_owned()
     }
 
-    fn detect(&mut self) -> f64 {
-        let data = self.driver.detect();
+    fn get_scans(&self) -> &Vec<Scan> {
+        &self.scans
+    }
+
+    fn set_scan_kind(&mut self, scan_idx: usize, kind: ScanKind) {
+        if let Some(scan) = self.scans.get_mut(scan_idx) {
+            scan.kind = kind;
+        }
+    }
+
+    fn detect(&mut self, position: f64) -> Result<f64, serialport::Error> {
+        let reading = self.driver.lock().unwrap().detect();
 
-        // Put the data into the last vector in the vector of vectors.
-        let retval = data.clone().unwrap();
-        self.scans.last_mut().unwrap().push(data.unwrap());
-        retval
+        match reading {
+            Ok(reading) => self.record(position, reading, unix_time()),
+            Err(ref e) => self.set_error(e.to_string()),
+        }
+        reading
     }
 
-    fn short_name(&mut self) {
-        todo!()
+    /// Puts a reading taken elsewhere, e.g. by a scan thread, into the last scan.
+    fn record(&mut self, position: f64, reading: f64, timestamp: f64) {
+        // A scan begins with its first reading, which may be well after it was started if it was left empty.
+        if self.scans.last().unwrap().readings.is_empty() {
+            self.scans.last_mut().unwrap().settings = self.settings_snapshot();
+        }
+
+        let scan = self.scans.last_mut().unwrap();
+        scan.positions.push(position);
+        scan.readings.push(reading);
+        scan.timestamps.push(timestamp);
+
+        self.latest = Some(reading);
     }
 
-    fn long_name(&mut self) {
-        todo!()
+    fn latest(&self) -> Option<f64> {
+        self.latest
+    }
+
+    fn is_overflow(&self) -> bool {
+        self.latest.is_some_and(|r| r.abs() >= drivers::OVERFLOW)
+    }
+
+    fn range(&self) -> String {
+        self.range.clone()
+    }
+
+    fn capabilities(&self) -> drivers::DetectorCapabilities {
+        self.capabilities
+    }
+
+    fn settings(&self) -> &[drivers::DriverSetting] {
+        &self.settings
+    }
+
+    fn set_setting(&mut self, name: &str, value: drivers::SettingValue) -> Result<(), serialport::Error> {
+        let mut drv = self.driver.lock().unwrap();
+        drv.set_setting(name, value.clone())?;
+        self.settings = drv.settings();
+        self.range = drv.range();
+        drop(drv);
+
+        keep_setting(&mut self.saved_settings, name, value);
+        Ok(())
+    }
+
+    /// Gives the driver settings saved with the profile, as after connecting.
+    fn restore_settings(&mut self, saved: Vec<(String, drivers::SettingValue)>) -> Result<(), serialport::Error> {
+        let mut drv = self.driver.lock().unwrap();
+        for (name, value) in saved.iter() {
+            drv.set_setting(name, value.clone())?;
+        }
+        self.settings = drv.settings();
+        self.range = drv.range();
+        drop(drv);
+
+        self.saved_settings = saved;
+        Ok(())
+    }
+
+    fn saved_settings(&self) -> &[(String, drivers::SettingValue)] {
+        &self.saved_settings
+    }
+
+    /// Has the detector measure its offset, with the input shunted, and correct readings by it from now on.
+    fn acquire_zero(&mut self) -> Result<(), serialport::Error> {
+        let mut drv = self.driver.lock().unwrap();
+        drv.acquire_zero()?;
+        self.settings = drv.settings();
+        self.range = drv.range();
+
+        Ok(())
+    }
+
+    fn set_error(&mut self, error: String) {
+        self.last_error = Some(error);
+    }
+
+    fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    fn port_name(&self) -> String {
+        self.port_name.clone()
+    }
+
+    fn short_name(&self) -> String {
+        self.short_name.clone()
+    }
+
+    fn long_name(&self) -> String {
+        self.long_name.clone()
+    }
+
+    fn identification(&self) -> String {
+        self.identification.clone()
     }
 }
\ No newline at end of file
@@ -0,0 +1,141 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread::{self, sleep};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::middleware::{self, Axis, BacklashMode, MotionControlMiddleware, MotionController, MotionState};
+
+// Manual movements of a single axis, as asked for from the Device Controls. Each runs on its own thread, since homing
+// may take minutes, and reports once when done. The status of the controller shows the movement meanwhile.
+
+/// The GUI's handle on a manual movement.
+pub struct MotionHandle {
+    pub axis: Axis,
+    pub result: Receiver<Result<(), String>>,
+    held: Option<Arc<AtomicBool>>, // Set while a continuous jog should go on.
+}
+
+impl MotionHandle {
+    /// Ends a continuous jog. Other movements run to completion regardless.
+    pub fn release(&self) {
+        if let Some(held) = &self.held {
+            held.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Homes the controller driving `axis`.
+pub fn home(axis: Axis, mc: &MotionController, ctx: egui::Context) -> MotionHandle {
+    let driver = mc.driver.clone();
+    let status = mc.shared_status();
+
+    spawn(axis, ctx, move || {
+        log::info!("Homing the {}.", axis.as_str());
+        middleware::run_motion(&driver, &status, MotionState::Homing, |drv| drv.home())
+            .map_err(|e| format!("Homing the {} failed: {}", axis.as_str(), e))
+    })
+}
+
+/// Moves the controller driving `axis` to `value`, in the physical unit of the axis.
+pub fn move_to(axis: Axis, mc: &MotionController, value: f64, ctx: egui::Context) -> MotionHandle {
+    let driver = mc.driver.clone();
+    let status = mc.shared_status();
+    let config = mc.config().clone();
+
+    spawn(axis, ctx, move || {
+        let fail = |e: String| format!("Moving the {} to {} {} failed: {}", axis.as_str(), value, axis.unit(), e);
+
+        config.check_limits(value).map_err(fail)?;
+
+        log::info!("Moving the {} to {} {}.", axis.as_str(), value, axis.unit());
+        middleware::move_axis(&driver, &status, &config, value).map_err(|e| fail(e.to_string()))
+    })
+}
+
+/// Moves the controller driving `axis` by `distance` from wherever it is, in the physical unit of the axis.
+pub fn jog(axis: Axis, mc: &MotionController, distance: f64, ctx: egui::Context) -> MotionHandle {
+    let driver = mc.driver.clone();
+    let status = mc.shared_status();
+    let config = mc.config().clone();
+
+    spawn(axis, ctx, move || {
+        let fail = |e: String| format!("Jogging the {} by {} {} failed: {}", axis.as_str(), distance, axis.unit(), e);
+
+        let value = config.steps_to_value(middleware::axis_steps(&driver, &status)) + distance;
+        config.check_limits(value).map_err(fail)?;
+
+        log::debug!("Jogging the {} by {} {}.", axis.as_str(), distance, axis.unit());
+        middleware::move_axis(&driver, &status, &config, value).map_err(|e| fail(e.to_string()))
+    })
+}
+
+/// Moves the controller driving `axis` at its jog speed, in the direction of the sign of `direction`, until released.
+///
+/// The jog also ends on reaching a limit, if the axis has them.
+pub fn jog_held(axis: Axis, mc: &MotionController, direction: f64, ctx: egui::Context) -> MotionHandle {
+    let driver = mc.driver.clone();
+    let status = mc.shared_status();
+    let config = mc.config().clone();
+    let held = Arc::new(AtomicBool::new(true));
+    let still_held = held.clone();
+
+    let mut handle = spawn(axis, ctx, move || {
+        let fail = |e: String| format!("Jogging the {} failed: {}", axis.as_str(), e);
+
+        let start = config.steps_to_value(middleware::axis_steps(&driver, &status));
+        let room = match config.limits {
+            Some((_, max)) if direction > 0.0 => max - start,
+            Some((min, _)) => start - min,
+            None => f64::INFINITY,
+        };
+        if room <= 0.0 {
+            return Err(fail("The axis is at its limit.".to_owned()));
+        }
+        let max_time = Duration::try_from_secs_f64(room / config.jog_speed).unwrap_or(Duration::MAX);
+
+        log::debug!("Jogging the {} continuously.", axis.as_str());
+        middleware::run_motion(&driver, &status, MotionState::Moving, |drv| {
+            let began = Instant::now();
+            drv.jog(config.jog_velocity(direction))?;
+
+            while still_held.load(Ordering::Relaxed) && began.elapsed() < max_time {
+                sleep(Duration::from_millis(20));
+            }
+
+            drv.jog(0)?;
+            middleware::check_switches(drv, &status)?;
+
+            // The slack is taken up on the way, as it would be by a move in the same direction.
+            if config.backlash_mode == BacklashMode::Reversal {
+                status.lock().unwrap().slack = if direction > 0.0 { config.backlash_steps() } else { 0 };
+            }
+            Ok(())
+        })
+        .map_err(|e| fail(e.to_string()))
+    });
+
+    handle.held = Some(held);
+    handle
+}
+
+fn spawn(
+    axis: Axis,
+    ctx: egui::Context,
+    f: impl FnOnce() -> Result<(), String> + Send + 'static,
+) -> MotionHandle {
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        let result = f();
+        if let Err(e) = &result {
+            log::error!("{}", e);
+        }
+
+        crate::send_to_gui(&tx, &ctx, result);
+    });
+
+    MotionHandle { axis, result: rx, held: None }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPortInfo, SerialPortType};
+
+use crate::drivers::SettingValue;
+use crate::middleware::{Axis, AxisConfig};
+
+// A spectrometer setup: which instrument is on which port, and how each is assigned and calibrated.
+// The current profile is restored on launch. Profiles can also be exported and imported, to switch between the
+// different setups in the lab.
+// Port names of USB adapters may change between reboots, so devices are bound to their USB serial number where they
+// have one. Their port is then looked up again before connecting.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MachineProfile {
+    pub name: String,
+    pub motion_controllers: Vec<MotionControlSetup>,
+    pub detectors: Vec<DetectorSetup>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MotionControlSetup {
+    pub model: String,
+    pub port_name: String, // Where the device was last seen.
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    pub nickname: String,
+    pub axis: Option<Axis>,
+    pub config: AxisConfig,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DetectorSetup {
+    pub model: String,
+    pub port_name: String, // Where the device was last seen.
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    pub nickname: String,
+    pub role: Option<DetectorRole>,
+    #[serde(default)]
+    pub settings: Vec<(String, SettingValue)>, // Of the model of detector, by name, as changed from their defaults.
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DetectorRole {
+    Sample,
+    Reference,
+}
+
+impl MachineProfile {
+    /// Returns the setup of the motion controller expected on `port_name`.
+    pub fn motion_controller(&self, port_name: &str) -> Option<&MotionControlSetup> {
+        self.motion_controllers.iter().find(|s| s.port_name == port_name)
+    }
+
+    /// Returns the setup of the detector expected on `port_name`.
+    pub fn detector(&self, port_name: &str) -> Option<&DetectorSetup> {
+        self.detectors.iter().find(|s| s.port_name == port_name)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    pub fn read(path: &str) -> Result<MachineProfile, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+}
+
+/// Returns the serial number of the USB device on `port`, if it is one and reports it.
+pub fn usb_serial_number(port: &SerialPortInfo) -> Option<String> {
+    match &port.port_type {
+        SerialPortType::UsbPort(usb) => usb.serial_number.clone(),
+        _ => None,
+    }
+}
+
+/// Returns the name of the port the USB device with `serial_number` is on now.
+///
+/// Adapters with several ports may report the same serial number on each. The device is then taken to still be on
+/// `last_port`, where it was last seen, since the serial number cannot tell which of them it is on.
+pub fn find_port(ports: &[SerialPortInfo], serial_number: &str, last_port: &str) -> Option<String> {
+    let matches: Vec<&str> = ports
+        .iter()
+        .filter(|p| usb_serial_number(p).as_deref() == Some(serial_number))
+        .map(|p| p.port_name.as_str())
+        .collect();
+
+    match matches[..] {
+        [] => None,
+        [port_name] => Some(port_name.to_owned()),
+        _ => {
+            if !matches.contains(&last_port) {
+                log::warn!(
+                    "Serial number {} is on each of {}, so which is meant is unknown. Keeping {}.",
+                    serial_number,
+                    matches.join(", "),
+                    last_port
+                );
+            }
+            Some(last_port.to_owned())
+        }
+    }
+}
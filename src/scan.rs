@@ -0,0 +1,196 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread::{self, sleep};
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::middleware::{self, AxisConfig, HomeBehavior, MotionState, SharedDetectorDriver, SharedMotionControlDriver, SharedMotionStatus};
+use crate::units::AxisUnit;
+
+// Messages from the scan thread to the GUI, in the order they happen.
+pub enum ScanEvent {
+    NewScan, // A repeat begins; the following points belong to a new scan.
+    Moving { position: f64 },
+    Point { position: f64, readings: Vec<f64>, timestamp: f64 }, // One reading per detector, in connection order.
+    Finished,
+    Failed(String),
+}
+
+pub struct ScanDefinition {
+    pub positions: Vec<f64>, // nm
+    pub repeats: u32,
+    pub axis: AxisConfig, // Of the main drive.
+}
+
+impl ScanDefinition {
+    /// Steps evenly from `start` to `end` in `unit`, returning the positions in nm.
+    ///
+    /// The end point is included if it falls on a step. The direction is taken from `start` and `end`, not the sign of `step`.
+    pub fn positions(start: f64, end: f64, step: f64, unit: AxisUnit) -> Vec<f64> {
+        if step == 0.0 || !step.is_finite() {
+            return vec![unit.unit_to_nm(start)];
+        }
+
+        let step = step.abs() * (end - start).signum();
+        let n = ((end - start) / step + 1e-9).floor() as usize;
+
+        (0..=n).map(|i| unit.unit_to_nm(start + i as f64 * step)).collect()
+    }
+}
+
+/// The GUI's handle on a running scan.
+pub struct ScanHandle {
+    pub events: Receiver<ScanEvent>,
+    paused: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ScanHandle {
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// The scan stops after the point in progress.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs a scan on its own thread, moving the main drive to each position and reading every detector there.
+///
+/// A repaint is requested whenever an event is sent, so the GUI only redraws when there is something new to show.
+pub fn start(
+    def: ScanDefinition,
+    main_drive: SharedMotionControlDriver,
+    main_drive_status: SharedMotionStatus,
+    detectors: Vec<SharedDetectorDriver>,
+    ctx: egui::Context,
+) -> ScanHandle {
+    let (tx, rx) = channel();
+    let paused = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let handle = ScanHandle {
+        events: rx,
+        paused: paused.clone(),
+        stopped: stopped.clone(),
+    };
+
+    thread::spawn(move || {
+        let send = |event: ScanEvent| crate::send_to_gui(&tx, &ctx, event);
+
+        match run(&def, &main_drive, &main_drive_status, &detectors, &paused, &stopped, &send) {
+            Ok(_) => send(ScanEvent::Finished),
+            Err(e) => {
+                log::error!("Scan failed: {}", e);
+                send(ScanEvent::Failed(e));
+            }
+        }
+    });
+
+    handle
+}
+
+fn run(
+    def: &ScanDefinition,
+    main_drive: &SharedMotionControlDriver,
+    main_drive_status: &SharedMotionStatus,
+    detectors: &[SharedDetectorDriver],
+    paused: &AtomicBool,
+    stopped: &AtomicBool,
+    send: &dyn Fn(ScanEvent),
+) -> Result<(), String> {
+    log::info!(
+        "Starting scan of {} points, {} repeat(s).",
+        def.positions.len(),
+        def.repeats
+    );
+
+    if def.axis.home == HomeBehavior::BeforeScan {
+        log::info!("Homing the main drive before scanning.");
+        middleware::run_motion(main_drive, main_drive_status, MotionState::Homing, |drv| drv.home())
+            .map_err(|e| e.to_string())?;
+    }
+
+    for _ in 0..def.repeats.max(1) {
+        send(ScanEvent::NewScan);
+
+        for &position in def.positions.iter() {
+            while paused.load(Ordering::Relaxed) && !stopped.load(Ordering::Relaxed) {
+                sleep(Duration::from_millis(100));
+            }
+
+            if stopped.load(Ordering::Relaxed) {
+                log::info!("Scan stopped.");
+                return Ok(());
+            }
+
+            log::debug!("Moving the main drive to {} nm.", position);
+            send(ScanEvent::Moving { position });
+
+            middleware::move_axis(main_drive, main_drive_status, &def.axis, position).map_err(|e| e.to_string())?;
+
+            let timestamp = middleware::unix_time();
+            let mut readings = Vec::with_capacity(detectors.len());
+            for (i, det) in detectors.iter().enumerate() {
+                let reading = det.lock().unwrap().detect();
+                readings.push(reading.map_err(|e| format!("Detector {}: {}", i + 1, e))?);
+            }
+
+            send(ScanEvent::Point { position, readings, timestamp });
+        }
+    }
+
+    log::info!("Scan complete.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::drivers::ki_6485::Ki6485Virtual;
+    use crate::drivers::mp_789a_4::Mp789a4;
+
+    /// Scans a scripted 789A-4 over three points, stopping after the first if `stop` is set. Returns the points taken.
+    fn scan_789a_4(stop: bool) -> usize {
+        let main_drive: SharedMotionControlDriver = Arc::new(Mutex::new(Box::new(Mp789a4::scripted(1).0)));
+        let detectors: Vec<SharedDetectorDriver> = vec![Arc::new(Mutex::new(Box::new(Ki6485Virtual::new(String::new(), 1))))];
+        let def = ScanDefinition {
+            positions: vec![500.0, 510.0, 520.0],
+            repeats: 1,
+            axis: AxisConfig::default(),
+        };
+
+        let stopped = AtomicBool::new(false);
+        let points = Cell::new(0);
+        let send = |event| {
+            if let ScanEvent::Point { .. } = event {
+                points.set(points.get() + 1);
+                stopped.store(stop, Ordering::Relaxed);
+            }
+        };
+
+        run(&def, &main_drive, &SharedMotionStatus::default(), &detectors, &AtomicBool::new(false), &stopped, &send).unwrap();
+        points.get()
+    }
+
+    #[test]
+    fn scans_a_789a_4_to_the_end() {
+        assert_eq!(scan_789a_4(false), 3);
+    }
+
+    #[test]
+    fn stops_after_the_point_in_progress() {
+        assert_eq!(scan_789a_4(true), 1);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+// Spectroscopic units for the main drive axis.
+// Positions are always stored and commanded in nanometres; the selected unit only affects what the user sees and types.
+
+const HC_EV_NM: f64 = 1239.841984; // Planck constant times speed of light [eV nm].
+
+/// Returns `k / value`, or 0 where that is not finite, e.g. for the 0 nm of an unset scan.
+fn invert(k: f64, value: f64) -> f64 {
+    let inverse = k / value;
+    if inverse.is_finite() {
+        inverse
+    } else {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum AxisUnit {
+    Nanometre,
+    Angstrom,
+    Micrometre,
+    ElectronVolt,
+    Wavenumber,
+}
+
+impl AxisUnit {
+    pub const ALL: [AxisUnit; 5] = [
+        AxisUnit::Nanometre,
+        AxisUnit::Angstrom,
+        AxisUnit::Micrometre,
+        AxisUnit::ElectronVolt,
+        AxisUnit::Wavenumber,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            AxisUnit::Nanometre => "nm",
+            AxisUnit::Angstrom => "Å",
+            AxisUnit::Micrometre => "µm",
+            AxisUnit::ElectronVolt => "eV",
+            AxisUnit::Wavenumber => "cm⁻¹",
+        }
+    }
+
+    pub fn quantity(&self) -> &str {
+        match self {
+            AxisUnit::Nanometre | AxisUnit::Angstrom | AxisUnit::Micrometre => "Wavelength",
+            AxisUnit::ElectronVolt => "Photon Energy",
+            AxisUnit::Wavenumber => "Wavenumber",
+        }
+    }
+
+    /// Whether the unit is proportional to wavelength, rather than inverse to it.
+    pub fn is_wavelength(&self) -> bool {
+        self.quantity() == "Wavelength"
+    }
+
+    /// Returns e.g. "Wavelength [nm]", for axis labels and file headers.
+    pub fn axis_label(&self) -> String {
+        format!("{} [{}]", self.quantity(), self.as_str())
+    }
+
+    pub fn nm_to_unit(self, nm: f64) -> f64 {
+        match self {
+            AxisUnit::Nanometre => nm,
+            AxisUnit::Angstrom => nm * 10.0,
+            AxisUnit::Micrometre => nm / 1000.0,
+            AxisUnit::ElectronVolt => invert(HC_EV_NM, nm),
+            AxisUnit::Wavenumber => invert(1e7, nm),
+        }
+    }
+
+    pub fn unit_to_nm(self, value: f64) -> f64 {
+        match self {
+            AxisUnit::Nanometre => value,
+            AxisUnit::Angstrom => value / 10.0,
+            AxisUnit::Micrometre => value * 1000.0,
+            AxisUnit::ElectronVolt => invert(HC_EV_NM, value),
+            AxisUnit::Wavenumber => invert(1e7, value),
+        }
+    }
+
+    /// Converts a value in this unit to `other`.
+    pub fn convert(self, value: f64, other: AxisUnit) -> f64 {
+        other.nm_to_unit(self.unit_to_nm(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "{} != {}", a, b);
+    }
+
+    #[test]
+    fn converts_known_values() {
+        assert_close(AxisUnit::Nanometre.convert(500.0, AxisUnit::Angstrom), 5000.0);
+        assert_close(AxisUnit::Nanometre.convert(500.0, AxisUnit::Micrometre), 0.5);
+        assert_close(AxisUnit::Nanometre.convert(500.0, AxisUnit::ElectronVolt), 2.479683968);
+        assert_close(AxisUnit::Nanometre.convert(500.0, AxisUnit::Wavenumber), 20000.0);
+    }
+
+    #[test]
+    fn round_trips_between_all_units() {
+        for from in AxisUnit::ALL {
+            for to in AxisUnit::ALL {
+                let value = from.nm_to_unit(632.8);
+                assert_close(to.convert(from.convert(value, to), from), value);
+                assert_close(from.convert(value, to), to.nm_to_unit(632.8));
+            }
+        }
+    }
+
+    #[test]
+    fn zero_has_no_infinite_inverse() {
+        for unit in AxisUnit::ALL {
+            assert_eq!(unit.nm_to_unit(0.0), 0.0);
+            assert_eq!(unit.unit_to_nm(0.0), 0.0);
+        }
+    }
+}